@@ -0,0 +1,268 @@
+//! The `#[derive(Payload)]` macro for the `ccsds-packet` crate.
+//! Use it through `ccsds-packet`'s `derive` feature rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, LitStr, Member};
+
+/// Derives `AnyBitPattern`, `NoPadding`, `ConvertEndian` and `Payload` for a payload struct.
+///
+/// The struct must be `#[repr(C)]` (or `#[repr(transparent)]`), must not be generic,
+/// and every field must itself be `AnyBitPattern`, `NoPadding` and `ConvertEndian`.
+/// The generated code checks at compile time that the struct contains no padding,
+/// so a struct like this one fails to compile until the gap is filled with an explicit spare field:
+///
+/// ```compile_fail,E0080
+/// use ccsds_packet::Payload;
+///
+/// #[derive(Clone, Copy, Payload)]
+/// #[repr(C)]
+/// struct Padded {
+///     a: u8,
+///     b: u32,
+/// }
+/// ```
+///
+/// Attributes:
+///
+/// - `#[payload(unaligned)]` on the struct additionally checks that the struct has an alignment of 1,
+///   so it can be placed after headers of any length without introducing padding.
+/// - `#[payload(crate = "path::to::ccsds_packet")]` on the struct sets the path the generated code
+///   uses to refer to the `ccsds-packet` crate, for when it isn't reachable as `::ccsds_packet`
+///   (say, because it's been renamed or is re-exported from another crate). Defaults to `::ccsds_packet`.
+/// - `#[payload(endian = "big")]` or `#[payload(endian = "little")]` on a field
+///   fixes that field's wire byte order, regardless of the byte order the rest of the packet is converted to.
+#[proc_macro_derive(Payload, attributes(payload))]
+pub fn derive_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A field of the struct being derived on.
+struct Field {
+    member: Member,
+    ty: syn::Type,
+    endianness: Option<TokenStream2>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "`Payload` can't be derived for generic structs",
+        ));
+    }
+    check_repr(&input)?;
+    let StructAttrs { unaligned, krate } = parse_struct_attrs(&input)?;
+
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        _ => {
+            return Err(syn::Error::new(
+                name.span(),
+                "`Payload` can only be derived for structs",
+            ))
+        }
+    };
+
+    let fields = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(i.into()),
+            };
+            Ok(Field {
+                member,
+                ty: field.ty.clone(),
+                endianness: parse_field_attrs(field, &krate)?,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let converted = fields.iter().map(|field| {
+        let member = &field.member;
+        let order = match &field.endianness {
+            Some(endianness) => endianness.clone(),
+            None => quote!(order),
+        };
+        quote!(#member: #krate::ConvertEndian::convert_endian(self.#member, #order))
+    });
+
+    let field_infos = fields.iter().map(|field| {
+        let member = &field.member;
+        let ty = &field.ty;
+        let field_name = match member {
+            Member::Named(ident) => LitStr::new(&ident.to_string(), ident.span()),
+            Member::Unnamed(index) => LitStr::new(&index.index.to_string(), index.span),
+        };
+        let endianness = match &field.endianness {
+            Some(endianness) => quote!(::core::option::Option::Some(#endianness)),
+            None => quote!(::core::option::Option::None),
+        };
+        quote! {
+            #krate::FieldInfo {
+                name: #field_name,
+                offset: ::core::mem::offset_of!(#name, #member),
+                size: ::core::mem::size_of::<#ty>(),
+                type_name: ::core::stringify!(#ty),
+                endianness: #endianness,
+            }
+        }
+    });
+
+    let field_types = fields.iter().map(|field| &field.ty);
+    let field_sizes = fields.iter().map(|field| {
+        let ty = &field.ty;
+        quote!(+ ::core::mem::size_of::<#ty>())
+    });
+    let padding_message = format!("`{name}` contains padding");
+
+    let align_check = unaligned.then(|| {
+        let message = format!("`{name}` has an alignment greater than 1");
+        quote! {
+            ::core::assert!(::core::mem::align_of::<#name>() == 1, #message);
+        }
+    });
+
+    Ok(quote! {
+        // Safety: the struct is repr(C), and each field's type is checked to be AnyBitPattern below.
+        unsafe impl #krate::AnyBitPattern for #name {}
+
+        // Safety: the struct is repr(C), each field's type is checked to be NoPadding below,
+        // and the struct's size is checked to be the sum of its fields' sizes.
+        unsafe impl #krate::NoPadding for #name {}
+
+        impl #krate::ConvertEndian for #name {
+            fn convert_endian(self, order: #krate::Endianness) -> Self {
+                let _ = order;
+                Self {
+                    #( #converted, )*
+                }
+            }
+        }
+
+        impl #krate::Payload for #name {
+            const FIELDS: &'static [#krate::FieldInfo] = &[ #( #field_infos, )* ];
+        }
+
+        const _: () = {
+            const fn assert_payload_field<T: #krate::AnyBitPattern + #krate::NoPadding + #krate::ConvertEndian>() {}
+            #( assert_payload_field::<#field_types>(); )*
+
+            ::core::assert!(
+                ::core::mem::size_of::<#name>() == 0 #( #field_sizes )*,
+                #padding_message,
+            );
+            #align_check
+        };
+    })
+}
+
+/// Checks that the struct has a `repr` that gives it a well-defined layout.
+fn check_repr(input: &DeriveInput) -> syn::Result<()> {
+    let mut has_layout = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                has_layout = true;
+            } else if meta.input.peek(syn::token::Paren) {
+                // e.g. packed(2) or align(4); skip over the argument
+                let _content;
+                syn::parenthesized!(_content in meta.input);
+            }
+            Ok(())
+        })?;
+    }
+
+    if has_layout {
+        Ok(())
+    } else {
+        Err(syn::Error::new(
+            input.ident.span(),
+            "`Payload` requires `#[repr(C)]` or `#[repr(transparent)]`",
+        ))
+    }
+}
+
+/// The options given in the `#[payload(...)]` attributes on the struct.
+struct StructAttrs {
+    /// Whether `unaligned` was given.
+    unaligned: bool,
+    /// The path to the `ccsds-packet` crate.
+    krate: syn::Path,
+}
+
+/// Parses the `#[payload(...)]` attributes on the struct.
+fn parse_struct_attrs(input: &DeriveInput) -> syn::Result<StructAttrs> {
+    let mut unaligned = false;
+    let mut krate = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("payload"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("unaligned") {
+                unaligned = true;
+                Ok(())
+            } else if meta.path.is_ident("crate") {
+                let value: LitStr = meta.value()?.parse()?;
+                krate = Some(value.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unrecognized `payload` attribute; expected `unaligned` or `crate`"))
+            }
+        })?;
+    }
+
+    Ok(StructAttrs {
+        unaligned,
+        krate: krate.unwrap_or_else(|| syn::parse_quote!(::ccsds_packet)),
+    })
+}
+
+/// Parses the `#[payload(...)]` attributes on a field, returning the field's fixed endianness, if any.
+fn parse_field_attrs(field: &syn::Field, krate: &syn::Path) -> syn::Result<Option<TokenStream2>> {
+    let mut endianness = None;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("payload"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("endian") {
+                let value: LitStr = meta.value()?.parse()?;
+                endianness = Some(match value.value().as_str() {
+                    "big" => quote!(#krate::Endianness::Big),
+                    "little" => quote!(#krate::Endianness::Little),
+                    _ => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            "expected `\"big\"` or `\"little\"`",
+                        ))
+                    }
+                });
+                Ok(())
+            } else {
+                Err(meta.error("unrecognized `payload` attribute; expected `endian`"))
+            }
+        })?;
+    }
+
+    Ok(endianness)
+}
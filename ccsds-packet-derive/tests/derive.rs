@@ -0,0 +1,30 @@
+use ccsds_packet::{Endianness, Payload};
+
+mod reexport {
+    pub use ccsds_packet as packet;
+}
+
+#[derive(Clone, Copy, Payload)]
+#[payload(crate = "reexport::packet")]
+#[repr(C)]
+struct Housekeeping {
+    #[payload(endian = "big")]
+    voltage: u16,
+    mode: u8,
+    flags: u8,
+}
+
+#[test]
+fn crate_path_can_be_overridden() {
+    let fields = Housekeeping::FIELDS;
+    assert_eq!(fields.len(), 3);
+    assert_eq!(fields[0].name, "voltage");
+    assert_eq!(fields[0].endianness, Some(Endianness::Big));
+    assert_eq!(fields[2].offset, 3);
+}
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+use ccsds_packet::Payload;
+
+#[derive(Clone, Copy, Payload)]
+#[repr(C)]
+struct BadEndian {
+    #[payload(endian = "middle")]
+    a: u16,
+}
+
+fn main() {}
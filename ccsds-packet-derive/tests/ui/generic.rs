@@ -0,0 +1,9 @@
+use ccsds_packet::Payload;
+
+#[derive(Clone, Copy, Payload)]
+#[repr(C)]
+struct Generic<T> {
+    a: T,
+}
+
+fn main() {}
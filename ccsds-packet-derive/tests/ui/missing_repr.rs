@@ -0,0 +1,9 @@
+use ccsds_packet::Payload;
+
+#[derive(Clone, Copy, Payload)]
+struct NoRepr {
+    a: u8,
+    b: u8,
+}
+
+fn main() {}
@@ -0,0 +1,145 @@
+//! Sources of the current spacecraft time, for timestamping telemetry
+//! with `timestamp_with` on any of the telemetry packet types.
+//!
+//! Flight software implements [`TimeSource`] over whatever clock the platform has;
+//! [`SystemTimeSource`] reads the host's clock (with the `std` feature),
+//! and [`ManualClock`] is set by hand, for tests and simulations.
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use crate::Error;
+#[cfg(feature = "std")]
+use crate::TimeConfig;
+
+/// A source of the current spacecraft time.
+pub trait TimeSource {
+    /// Returns the current spacecraft time: the time since the flight-software epoch.
+    fn now(&self) -> Result<Duration, Error>;
+}
+
+impl<S: TimeSource + ?Sized> TimeSource for &S {
+    fn now(&self) -> Result<Duration, Error> {
+        (**self).now()
+    }
+}
+
+/// A [`TimeSource`] reading the host's clock, which is taken to keep UTC,
+/// for a spacecraft clock configured as in a [`TimeConfig`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTimeSource<'a> {
+    config: TimeConfig<'a>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> SystemTimeSource<'a> {
+    /// Creates a time source for a spacecraft clock configured as in `config`.
+    pub const fn new(config: TimeConfig<'a>) -> Self {
+        Self { config }
+    }
+
+    /// Returns the spacecraft clock's configuration.
+    pub const fn config(&self) -> TimeConfig<'a> {
+        self.config
+    }
+}
+
+#[cfg(feature = "std")]
+impl TimeSource for SystemTimeSource<'_> {
+    fn now(&self) -> Result<Duration, Error> {
+        self.config.from_utc(std::time::SystemTime::now().into())
+    }
+}
+
+/// A [`TimeSource`] that only moves when it's told to.
+///
+/// The time is kept in a [`Cell`], so it can be set or advanced through a shared reference
+/// while packets are being timestamped from it.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Cell<Duration>,
+}
+
+impl ManualClock {
+    /// Creates a clock reading `now`, the time since the flight-software epoch.
+    pub const fn new(now: Duration) -> Self {
+        Self {
+            now: Cell::new(now),
+        }
+    }
+
+    /// Sets the clock to `now`.
+    pub fn set(&self, now: Duration) {
+        self.now.set(now);
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&self) -> Result<Duration, Error> {
+        Ok(self.now.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Telemetry, TelemetryMsgId, TelemetryViewMut};
+
+    struct Broken;
+
+    impl TimeSource for Broken {
+        fn now(&self) -> Result<Duration, Error> {
+            Err(Error::TimeOutOfRange)
+        }
+    }
+
+    #[test]
+    fn manual_clock_moves_only_when_told() {
+        let clock = ManualClock::default();
+        assert_eq!(clock.now(), Ok(Duration::ZERO));
+
+        clock.set(Duration::from_secs(100));
+        assert_eq!(clock.now(), Ok(Duration::from_secs(100)));
+        clock.advance(Duration::from_millis(1500));
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), Ok(Duration::from_secs(103)));
+
+        // through a shared reference, too
+        let source: &dyn TimeSource = &&clock;
+        clock.set(Duration::from_secs(7));
+        assert_eq!(source.now(), Ok(Duration::from_secs(7)));
+        assert_eq!(
+            ManualClock::new(Duration::from_secs(5)).now(),
+            Ok(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn timestamp_with_reads_the_source() {
+        let clock = ManualClock::new(Duration::new(100, 500_000_000));
+        let mut tlm = Telemetry::new(TelemetryMsgId::new(0x0880), [0u8; 2]).unwrap();
+
+        tlm.timestamp_with(&clock).unwrap();
+        assert_eq!(tlm.timestamp(), (100, 0x8000));
+        clock.advance(Duration::from_millis(750));
+        tlm.timestamp_with(&clock).unwrap();
+        assert_eq!(tlm.timestamp(), (101, 0x4000));
+
+        // a failing source leaves the timestamp alone
+        assert_eq!(tlm.timestamp_with(&Broken), Err(Error::TimeOutOfRange));
+        assert_eq!(tlm.timestamp(), (101, 0x4000));
+
+        let mut bytes = tlm.as_bytes().to_vec();
+        let mut view =
+            TelemetryViewMut::<crate::MsgIdV1, crate::Cfe32_16>::new(&mut bytes).unwrap();
+        clock.set(Duration::from_secs(0x0102_0304));
+        view.timestamp_with(&clock).unwrap();
+        assert_eq!(&bytes[6..12], [0x01, 0x02, 0x03, 0x04, 0x00, 0x00]);
+    }
+}
@@ -0,0 +1,474 @@
+//! Packets whose payload length is only known at runtime, backed by a byte buffer
+//! rather than a fixed-size Rust structure.
+
+use core::marker::PhantomData;
+
+use crate::time::telemetry_header_len;
+use crate::{
+    cfs_primary_header, Cfe32_16, CommandMsgId, CommandView, CommandViewMut, Error, MsgIdScheme,
+    MsgIdV1, PrimaryHeader, TelemetryMsgId, TelemetryView, TelemetryViewMut, TimeFormat,
+    COMMAND_HEADER_LEN, MAX_FUNCTION_CODE,
+};
+
+/// Byte storage for a [`DynCommandPacket`] or [`DynTelemetryPacket`].
+pub trait PacketBuffer {
+    /// Returns the bytes currently in the buffer.
+    fn as_slice(&self) -> &[u8];
+
+    /// Returns the bytes currently in the buffer, for modification.
+    fn as_mut_slice(&mut self) -> &mut [u8];
+
+    /// Changes the number of bytes in the buffer to `len`, zero-filling any new bytes,
+    /// or returns an error (leaving the buffer unchanged) if the buffer can't hold `len` bytes.
+    fn resize(&mut self, len: usize) -> Result<(), Error>;
+
+    /// Returns the most bytes the buffer can hold, or `None` if it can grow as needed.
+    fn max_len(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+impl PacketBuffer for Vec<u8> {
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+
+    fn resize(&mut self, len: usize) -> Result<(), Error> {
+        Vec::resize(self, len, 0);
+        Ok(())
+    }
+}
+
+/// A packet buffer holding up to `N` bytes inline, for use without an allocator.
+#[derive(Clone, Debug)]
+pub struct ArrayBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ArrayBuffer<N> {
+    /// Returns a new, empty buffer.
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the number of bytes the buffer can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> Default for ArrayBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PacketBuffer for ArrayBuffer<N> {
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[..self.len]
+    }
+
+    fn resize(&mut self, len: usize) -> Result<(), Error> {
+        resize_in_place(&mut self.bytes, &mut self.len, len)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(N)
+    }
+}
+
+/// A packet buffer borrowing caller-provided storage, of which it uses as much as the packet needs.
+#[derive(Debug)]
+pub struct SliceBuffer<'a> {
+    bytes: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceBuffer<'a> {
+    /// Returns a new, empty buffer using `bytes` as its storage.
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        Self { bytes, len: 0 }
+    }
+
+    /// Returns a buffer using `bytes` as its storage, initially holding all of `bytes`
+    /// (say, a packet that's just been received).
+    pub fn full(bytes: &'a mut [u8]) -> Self {
+        let len = bytes.len();
+        Self { bytes, len }
+    }
+
+    /// Returns the number of bytes the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl PacketBuffer for SliceBuffer<'_> {
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[..self.len]
+    }
+
+    fn resize(&mut self, len: usize) -> Result<(), Error> {
+        resize_in_place(self.bytes, &mut self.len, len)
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        Some(self.bytes.len())
+    }
+}
+
+/// Resizes the first `*len` bytes of `storage` to `new_len` bytes, zero-filling any new bytes.
+fn resize_in_place(storage: &mut [u8], len: &mut usize, new_len: usize) -> Result<(), Error> {
+    if new_len > storage.len() {
+        return Err(Error::CapacityExceeded {
+            capacity: storage.len(),
+            needed: new_len,
+        });
+    }
+    if new_len > *len {
+        storage[*len..new_len].fill(0);
+    }
+    *len = new_len;
+    Ok(())
+}
+
+/// Replaces everything after the first `header_len` bytes of `buffer` with `payload`,
+/// updating the packet data length field to match.
+fn replace_payload<B: PacketBuffer>(
+    buffer: &mut B,
+    header_len: usize,
+    payload: &[u8],
+) -> Result<(), Error> {
+    let len = header_len + payload.len();
+
+    let mut primary =
+        PrimaryHeader::from_bytes(buffer.as_slice()[..PrimaryHeader::LEN].try_into().unwrap());
+    primary.set_packet_len(len)?;
+    buffer.resize(len)?;
+
+    let bytes = buffer.as_mut_slice();
+    bytes[..PrimaryHeader::LEN].copy_from_slice(primary.as_bytes());
+    bytes[header_len..].copy_from_slice(payload);
+    Ok(())
+}
+
+/// A cFS-flavor CCSDS command packet whose payload length is only known at runtime,
+/// stored in a [`PacketBuffer`], whose message ID is mapped onto the headers by `M`.
+///
+/// Header fields are accessed through [`Self::view`] and [`Self::view_mut`].
+#[derive(Clone, Debug)]
+pub struct DynCommandPacket<B: PacketBuffer, M: MsgIdScheme> {
+    buffer: B,
+    msg_ids: PhantomData<M>,
+}
+
+/// A cFS-flavor CCSDS telemetry packet whose payload length is only known at runtime,
+/// stored in a [`PacketBuffer`], whose message ID is mapped onto the headers by `M`
+/// and whose timestamp is in the time format `F`.
+///
+/// Header fields are accessed through [`Self::view`] and [`Self::view_mut`].
+#[derive(Clone, Debug)]
+pub struct DynTelemetryPacket<B: PacketBuffer, M: MsgIdScheme, F: TimeFormat> {
+    buffer: B,
+    msg_ids: PhantomData<M>,
+    time_format: PhantomData<F>,
+}
+
+/// A runtime-sized command packet using cFE's default message ID mapping.
+pub type DynCommand<B> = DynCommandPacket<B, MsgIdV1>;
+
+/// A runtime-sized telemetry packet using cFE's default message ID mapping and packet time format.
+pub type DynTelemetry<B> = DynTelemetryPacket<B, MsgIdV1, Cfe32_16>;
+
+impl<B: PacketBuffer, M: MsgIdScheme> DynCommandPacket<B, M> {
+    /// If `function_code` is a permissible command code,
+    /// writes a new command with a copy of `payload` as its payload into `buffer`; otherwise returns an error.
+    pub fn new_in(
+        mut buffer: B,
+        msg_id: CommandMsgId<M>,
+        function_code: u16,
+        payload: &[u8],
+    ) -> Result<Self, Error> {
+        // check that fields are in their allowed ranges
+        if function_code > MAX_FUNCTION_CODE {
+            return Err(Error::InvalidFunctionCode(function_code));
+        }
+
+        let len = COMMAND_HEADER_LEN + payload.len();
+        let primary = cfs_primary_header::<M>(msg_id.get(), len)?;
+        buffer.resize(len)?;
+
+        let bytes = buffer.as_mut_slice();
+        bytes[..PrimaryHeader::LEN].copy_from_slice(primary.as_bytes());
+        // cFS secondary header for commands: command code and optional checksum
+        bytes[PrimaryHeader::LEN..COMMAND_HEADER_LEN].copy_from_slice(&[function_code as u8, 0x00]);
+        bytes[COMMAND_HEADER_LEN..].copy_from_slice(payload);
+
+        Ok(Self {
+            buffer,
+            msg_ids: PhantomData,
+        })
+    }
+
+    /// If `buffer` holds exactly one command packet with sane header values, wraps it.
+    pub fn from_buffer(buffer: B) -> Result<Self, Error> {
+        CommandView::<M>::new(buffer.as_slice())?;
+        Ok(Self {
+            buffer,
+            msg_ids: PhantomData,
+        })
+    }
+
+    /// Returns the buffer holding the packet.
+    pub fn into_buffer(self) -> B {
+        self.buffer
+    }
+
+    /// Returns a view of the packet, for access to its header fields.
+    pub fn view(&self) -> CommandView<'_, M> {
+        CommandView::new_unchecked(self.buffer.as_slice())
+    }
+
+    /// Returns a mutable view of the packet, for modifying its header fields.
+    pub fn view_mut(&mut self) -> CommandViewMut<'_, M> {
+        CommandViewMut::new_unchecked(self.buffer.as_mut_slice())
+    }
+
+    /// Returns the whole packet as a sequence of bytes, ready for transmission.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buffer.as_slice()
+    }
+
+    /// Returns the message's payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_slice()[COMMAND_HEADER_LEN..]
+    }
+
+    /// Returns the message's payload for modification.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut_slice()[COMMAND_HEADER_LEN..]
+    }
+
+    /// Replaces the message's payload with a copy of `payload`,
+    /// updating the packet data length field and the checksum to match.
+    ///
+    /// If the payload doesn't fit, the message is left as it was.
+    pub fn set_payload(&mut self, payload: &[u8]) -> Result<(), Error> {
+        replace_payload(&mut self.buffer, COMMAND_HEADER_LEN, payload)?;
+        self.view_mut().set_checksum();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<M: MsgIdScheme> DynCommandPacket<Vec<u8>, M> {
+    /// [`Self::new_in`], allocating a new buffer.
+    pub fn new(msg_id: CommandMsgId<M>, function_code: u16, payload: &[u8]) -> Result<Self, Error> {
+        Self::new_in(Vec::new(), msg_id, function_code, payload)
+    }
+}
+
+impl<B: PacketBuffer, M: MsgIdScheme, F: TimeFormat> DynTelemetryPacket<B, M, F> {
+    /// Writes a new telemetry message with a copy of `payload` as its payload into `buffer`,
+    /// or returns an error if the packet doesn't fit.
+    pub fn new_in(mut buffer: B, msg_id: TelemetryMsgId<M>, payload: &[u8]) -> Result<Self, Error> {
+        let header_len = telemetry_header_len::<F>();
+        let len = header_len + payload.len();
+        let primary = cfs_primary_header::<M>(msg_id.get(), len)?;
+        buffer.resize(len)?;
+
+        let bytes = buffer.as_mut_slice();
+        bytes[..PrimaryHeader::LEN].copy_from_slice(primary.as_bytes());
+        // cFS secondary header for telemetry (timestamp and any structure padding) starts zeroed
+        bytes[PrimaryHeader::LEN..header_len].fill(0);
+        bytes[header_len..].copy_from_slice(payload);
+
+        Ok(Self {
+            buffer,
+            msg_ids: PhantomData,
+            time_format: PhantomData,
+        })
+    }
+
+    /// If `buffer` holds exactly one telemetry packet with sane header values, wraps it.
+    pub fn from_buffer(buffer: B) -> Result<Self, Error> {
+        TelemetryView::<M, F>::new(buffer.as_slice())?;
+        Ok(Self {
+            buffer,
+            msg_ids: PhantomData,
+            time_format: PhantomData,
+        })
+    }
+
+    /// Returns the buffer holding the packet.
+    pub fn into_buffer(self) -> B {
+        self.buffer
+    }
+
+    /// Returns a view of the packet, for access to its header fields.
+    pub fn view(&self) -> TelemetryView<'_, M, F> {
+        TelemetryView::new_unchecked(self.buffer.as_slice())
+    }
+
+    /// Returns a mutable view of the packet, for modifying its header fields.
+    pub fn view_mut(&mut self) -> TelemetryViewMut<'_, M, F> {
+        TelemetryViewMut::new_unchecked(self.buffer.as_mut_slice())
+    }
+
+    /// Returns the whole packet as a sequence of bytes, ready for transmission.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buffer.as_slice()
+    }
+
+    /// Returns the message's payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_slice()[telemetry_header_len::<F>()..]
+    }
+
+    /// Returns the message's payload for modification.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut_slice()[telemetry_header_len::<F>()..]
+    }
+
+    /// Replaces the message's payload with a copy of `payload`, updating the packet data length field to match.
+    ///
+    /// If the payload doesn't fit, the message is left as it was.
+    pub fn set_payload(&mut self, payload: &[u8]) -> Result<(), Error> {
+        replace_payload(&mut self.buffer, telemetry_header_len::<F>(), payload)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<M: MsgIdScheme, F: TimeFormat> DynTelemetryPacket<Vec<u8>, M, F> {
+    /// [`Self::new_in`], allocating a new buffer.
+    pub fn new(msg_id: TelemetryMsgId<M>, payload: &[u8]) -> Result<Self, Error> {
+        Self::new_in(Vec::new(), msg_id, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cfe_checksum, Command};
+
+    #[test]
+    fn set_payload_updates_length_and_checksum() {
+        let mut cmd = DynCommand::new_in(
+            ArrayBuffer::<32>::new(),
+            CommandMsgId::new(0x1880),
+            2,
+            &[1, 2],
+        )
+        .unwrap();
+        assert_eq!(cmd.as_bytes()[4..8], [0x00, 0x03, 0x02, 0x00]);
+
+        cmd.set_payload(&[1, 2, 3, 4, 5]).unwrap();
+        assert_eq!(cmd.as_bytes().len(), 13);
+        assert_eq!(cmd.view().primary_header().packet_len(), 13);
+        assert_eq!(cmd.payload(), [1, 2, 3, 4, 5]);
+        assert!(cmd.view().is_checksum_valid());
+        assert_eq!(cfe_checksum(cmd.as_bytes()), 0);
+
+        // the same packet as a fixed-size command
+        let mut fixed = Command::new(CommandMsgId::new(0x1880), 2, [1u8, 2, 3, 4, 5]).unwrap();
+        assert_eq!(cmd.as_bytes(), fixed.as_bytes_with_checksum());
+
+        let mut tlm =
+            DynTelemetry::new_in(ArrayBuffer::<32>::new(), TelemetryMsgId::new(0x0880), &[])
+                .unwrap();
+        assert_eq!(tlm.as_bytes().len(), 16);
+        tlm.set_payload(&[9; 3]).unwrap();
+        assert_eq!(tlm.view().primary_header().packet_len(), 19);
+        assert_eq!(tlm.view().payload(), [9; 3]);
+    }
+
+    #[test]
+    fn fixed_capacity_buffers_reject_long_packets() {
+        assert_eq!(
+            DynCommand::new_in(
+                ArrayBuffer::<10>::new(),
+                CommandMsgId::new(0x1880),
+                0,
+                &[0; 3]
+            )
+            .err(),
+            Some(Error::CapacityExceeded {
+                capacity: 10,
+                needed: 11
+            })
+        );
+
+        let mut storage = [0xEE; 20];
+        let mut tlm = DynTelemetry::new_in(
+            SliceBuffer::new(&mut storage),
+            TelemetryMsgId::new(0x0880),
+            &[1; 4],
+        )
+        .unwrap();
+        assert_eq!(
+            tlm.set_payload(&[2; 5]),
+            Err(Error::CapacityExceeded {
+                capacity: 20,
+                needed: 21
+            })
+        );
+        assert_eq!(tlm.payload(), [1; 4]);
+        assert_eq!(tlm.view().primary_header().packet_len(), 20);
+
+        let buffer = tlm.into_buffer();
+        assert_eq!(buffer.capacity(), 20);
+        assert_eq!(buffer.as_slice().len(), 20);
+    }
+
+    #[test]
+    fn from_buffer_checks_the_packet() {
+        let mut fixed = Command::new(CommandMsgId::new(0x1880), 1, [7u8; 4]).unwrap();
+        let mut bytes = fixed.as_bytes_with_checksum().to_vec();
+
+        let cmd = DynCommand::from_buffer(SliceBuffer::full(&mut bytes)).unwrap();
+        assert_eq!(cmd.view().function_code(), 1);
+        assert_eq!(cmd.payload(), [7; 4]);
+
+        assert!(DynTelemetry::from_buffer(SliceBuffer::full(&mut bytes)).is_err());
+        assert_eq!(
+            DynCommand::from_buffer(SliceBuffer::full(&mut bytes[..10])).err(),
+            Some(Error::LengthFieldMismatch {
+                expected: 10,
+                actual: 12
+            })
+        );
+        assert_eq!(
+            DynCommand::from_buffer(SliceBuffer::full(&mut bytes[..4])).err(),
+            Some(Error::Truncated {
+                expected: 8,
+                actual: 4
+            })
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn vec_buffers_grow() {
+        let mut tlm = DynTelemetry::new(TelemetryMsgId::new(0x0880), &[]).unwrap();
+        tlm.set_payload(&[3; 1000]).unwrap();
+        assert_eq!(tlm.view().primary_header().packet_len(), 1016);
+        assert_eq!(tlm.into_buffer().len(), 1016);
+    }
+}
@@ -0,0 +1,334 @@
+/// A byte order for multi-byte values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    /// Most significant byte first.
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+impl Endianness {
+    /// The byte order of the host we're running on.
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Self = Endianness::Big;
+
+    /// The byte order of the host we're running on.
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Self = Endianness::Little;
+
+    /// Returns whether this is the byte order of the host we're running on.
+    pub const fn is_native(self) -> bool {
+        matches!(
+            (self, Self::NATIVE),
+            (Endianness::Big, Endianness::Big) | (Endianness::Little, Endianness::Little)
+        )
+    }
+}
+
+/// Types whose in-memory representation can be converted between host byte order and a given wire byte order.
+///
+/// Packet headers are always big-endian, regardless of host, and are handled by this crate;
+/// payloads are stored in host byte order in [`Command`](crate::Command) and [`Telemetry`](crate::Telemetry),
+/// and this trait is how they're converted to and from the byte order the flight software uses
+/// (see [`Command::to_wire`](crate::Command::to_wire) and [`Command::to_host`](crate::Command::to_host)).
+///
+/// This is implemented for the primitive types and arrays of them,
+/// for payload structs declared with [`payload!`](crate::payload),
+/// and (as a no-op) for the fixed-byte-order field types like [`U32Be`].
+/// Payloads built entirely out of fixed-byte-order field types
+/// are laid out the same way on every host and don't need converting at all.
+pub trait ConvertEndian: Copy {
+    /// Converts `self` from host byte order to `order`, or equivalently from `order` to host byte order.
+    fn convert_endian(self, order: Endianness) -> Self;
+}
+
+macro_rules! impl_convert_endian_int {
+    ($($ty:ty),*) => {
+        $(
+            impl ConvertEndian for $ty {
+                fn convert_endian(self, order: Endianness) -> Self {
+                    if order.is_native() {
+                        self
+                    } else {
+                        self.swap_bytes()
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_convert_endian_float {
+    ($($ty:ty),*) => {
+        $(
+            impl ConvertEndian for $ty {
+                fn convert_endian(self, order: Endianness) -> Self {
+                    <$ty>::from_bits(self.to_bits().convert_endian(order))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_convert_endian_identity {
+    ($($ty:ty),*) => {
+        $(
+            impl ConvertEndian for $ty {
+                fn convert_endian(self, _order: Endianness) -> Self {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_convert_endian_int!(u16, u32, u64, u128, usize, i16, i32, i64, i128, isize);
+impl_convert_endian_float!(f32, f64);
+impl_convert_endian_identity!(u8, i8, bool, ());
+
+impl<T: ConvertEndian, const N: usize> ConvertEndian for [T; N] {
+    fn convert_endian(self, order: Endianness) -> Self {
+        self.map(|elem| elem.convert_endian(order))
+    }
+}
+
+macro_rules! endian_wrapper {
+    ($name:ident, $ty:ty, $order:literal, $to_bytes:ident, $from_bytes:ident $(, $const:tt)?) => {
+        #[doc = concat!("A `", stringify!($ty), "` stored ", $order, "-endian regardless of host byte order,")]
+        /// with an alignment of 1, for use as a field in payload structs.
+        ///
+        /// The wire representation is fixed, so [`ConvertEndian`] leaves it alone.
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Default)]
+        pub struct $name([u8; core::mem::size_of::<$ty>()]);
+
+        impl $name {
+            /// Wraps `value`, storing it in this type's byte order.
+            pub $($const)? fn new(value: $ty) -> Self {
+                Self(value.$to_bytes())
+            }
+
+            /// Returns the stored value, converted to host byte order.
+            pub $($const)? fn get(self) -> $ty {
+                <$ty>::$from_bytes(self.0)
+            }
+
+            /// Stores `value`, in this type's byte order.
+            pub fn set(&mut self, value: $ty) {
+                self.0 = value.$to_bytes();
+            }
+
+            /// Returns the stored bytes.
+            pub const fn to_bytes(self) -> [u8; core::mem::size_of::<$ty>()] {
+                self.0
+            }
+
+            /// Wraps bytes already in this type's byte order.
+            pub const fn from_bytes(bytes: [u8; core::mem::size_of::<$ty>()]) -> Self {
+                Self(bytes)
+            }
+        }
+
+        impl From<$ty> for $name {
+            fn from(value: $ty) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(value: $name) -> Self {
+                value.get()
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.get() == other.get()
+            }
+        }
+
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Debug::fmt(&self.get(), f)
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Display::fmt(&self.get(), f)
+            }
+        }
+
+        // Safety: a repr(transparent) wrapper around a byte array has no padding,
+        // and every byte pattern is a valid value.
+        unsafe impl crate::AnyBitPattern for $name {}
+        unsafe impl crate::NoPadding for $name {}
+
+        impl ConvertEndian for $name {
+            fn convert_endian(self, _order: Endianness) -> Self {
+                self
+            }
+        }
+    };
+}
+
+macro_rules! endian_int {
+    ($($be:ident, $le:ident: $ty:ty;)*) => {
+        $(
+            endian_wrapper!($be, $ty, "big", to_be_bytes, from_be_bytes, const);
+            endian_wrapper!($le, $ty, "little", to_le_bytes, from_le_bytes, const);
+
+            impl Eq for $be {}
+            impl Eq for $le {}
+
+            impl core::hash::Hash for $be {
+                fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                    self.get().hash(state);
+                }
+            }
+
+            impl core::hash::Hash for $le {
+                fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                    self.get().hash(state);
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! endian_float {
+    ($($be:ident, $le:ident: $ty:ty;)*) => {
+        $(
+            // converting floats to and from bytes is only const from Rust 1.83
+            endian_wrapper!($be, $ty, "big", to_be_bytes, from_be_bytes);
+            endian_wrapper!($le, $ty, "little", to_le_bytes, from_le_bytes);
+        )*
+    };
+}
+
+endian_int! {
+    U16Be, U16Le: u16;
+    U32Be, U32Le: u32;
+    U64Be, U64Le: u64;
+    U128Be, U128Le: u128;
+    I16Be, I16Le: i16;
+    I32Be, I32Le: i32;
+    I64Be, I64Le: i64;
+    I128Be, I128Le: i128;
+}
+
+endian_float! {
+    F32Be, F32Le: f32;
+    F64Be, F64Le: f64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrappers_store_their_byte_order() {
+        assert_eq!(U16Be::new(0x0102).to_bytes(), [0x01, 0x02]);
+        assert_eq!(U16Le::new(0x0102).to_bytes(), [0x02, 0x01]);
+        assert_eq!(U32Be::new(0x0102_0304).to_bytes(), [1, 2, 3, 4]);
+        assert_eq!(U32Le::new(0x0102_0304).to_bytes(), [4, 3, 2, 1]);
+        assert_eq!(
+            U64Be::new(0x0102_0304_0506_0708).to_bytes(),
+            [1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        assert_eq!(
+            U64Le::new(0x0102_0304_0506_0708).to_bytes(),
+            [8, 7, 6, 5, 4, 3, 2, 1]
+        );
+        let u128 = u128::from_be_bytes(core::array::from_fn(|i| i as u8 + 1));
+        assert_eq!(
+            U128Be::new(u128).to_bytes(),
+            core::array::from_fn(|i| i as u8 + 1)
+        );
+        assert_eq!(
+            U128Le::new(u128).to_bytes(),
+            core::array::from_fn(|i| 16 - i as u8)
+        );
+
+        assert_eq!(I16Be::new(-2).to_bytes(), [0xFF, 0xFE]);
+        assert_eq!(I16Le::new(-2).to_bytes(), [0xFE, 0xFF]);
+        assert_eq!(I32Be::new(-2).to_bytes(), [0xFF, 0xFF, 0xFF, 0xFE]);
+        assert_eq!(I32Le::new(-2).to_bytes(), [0xFE, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(I64Be::new(i64::MIN).to_bytes(), [0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(I64Le::new(i64::MIN).to_bytes(), [0, 0, 0, 0, 0, 0, 0, 0x80]);
+        assert_eq!(I128Be::new(i128::MIN).to_bytes()[0], 0x80);
+        assert_eq!(I128Le::new(i128::MIN).to_bytes()[15], 0x80);
+
+        assert_eq!(F32Be::new(1.0).to_bytes(), [0x3F, 0x80, 0x00, 0x00]);
+        assert_eq!(F32Le::new(1.0).to_bytes(), [0x00, 0x00, 0x80, 0x3F]);
+        assert_eq!(F64Be::new(-2.0).to_bytes(), [0xC0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(F64Le::new(-2.0).to_bytes(), [0, 0, 0, 0, 0, 0, 0, 0xC0]);
+
+        let mut value = U32Be::from_bytes([0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(value.get(), 0xDEAD_BEEF);
+        value.set(0x0A0B_0C0D);
+        assert_eq!(value.to_bytes(), [0x0A, 0x0B, 0x0C, 0x0D]);
+        assert_eq!(core::mem::align_of::<U64Le>(), 1);
+    }
+
+    #[test]
+    fn wrappers_round_trip() {
+        for value in [0, 1, 0x1234, u16::MAX] {
+            assert_eq!(U16Be::new(value).get(), value);
+            assert_eq!(U16Le::new(value).get(), value);
+            assert_eq!(u16::from(U16Be::from(value)), value);
+        }
+        for value in [i64::MIN, -1, 0, 1, i64::MAX] {
+            assert_eq!(I64Be::new(value).get(), value);
+            assert_eq!(I64Le::new(value).get(), value);
+        }
+        for value in [0.0, -0.0, 1.5, f64::MIN_POSITIVE, f64::INFINITY] {
+            assert_eq!(F64Be::new(value).get().to_bits(), value.to_bits());
+            assert_eq!(F64Le::new(value).get().to_bits(), value.to_bits());
+        }
+
+        // NaN payloads and signs survive, even though NaN != NaN
+        for bits in [0x7FC0_0001, 0xFFC0_1234, 0x7F80_0001] {
+            let nan = f32::from_bits(bits);
+            assert_eq!(F32Be::new(nan).get().to_bits(), bits);
+            assert_eq!(F32Le::new(nan).get().to_bits(), bits);
+            assert_eq!(F32Be::new(nan).to_bytes(), bits.to_be_bytes());
+        }
+        let nan = f64::from_bits(0xFFF8_0000_DEAD_BEEF);
+        assert_eq!(F64Le::new(nan).get().to_bits(), 0xFFF8_0000_DEAD_BEEF);
+    }
+
+    #[test]
+    fn convert_endian_swaps_only_for_the_foreign_order() {
+        let foreign = match Endianness::NATIVE {
+            Endianness::Big => Endianness::Little,
+            Endianness::Little => Endianness::Big,
+        };
+        assert!(Endianness::NATIVE.is_native() && !foreign.is_native());
+
+        assert_eq!(0x0102u16.convert_endian(Endianness::NATIVE), 0x0102);
+        assert_eq!(0x0102u16.convert_endian(foreign), 0x0201);
+        assert_eq!((-2i32).convert_endian(foreign), -0x0100_0001);
+        assert_eq!(
+            f32::from_bits(0x3F80_0000)
+                .convert_endian(foreign)
+                .to_bits(),
+            0x0000_803F
+        );
+        assert_eq!(0xABu8.convert_endian(foreign), 0xAB);
+
+        let array = [0x0102u16, 0x0304, 0x0506];
+        assert_eq!(array.convert_endian(Endianness::NATIVE), array);
+        assert_eq!(array.convert_endian(foreign), [0x0201, 0x0403, 0x0605]);
+        assert_eq!(array.convert_endian(foreign).convert_endian(foreign), array);
+        assert_eq!(
+            [[0x0102_0304u32; 2]; 2].convert_endian(foreign),
+            [[0x0403_0201; 2]; 2]
+        );
+
+        // the fixed-order wrappers are already in their wire order
+        let wrapped = [U16Be::new(0x0102), U16Be::new(0x0304)];
+        assert_eq!(wrapped.convert_endian(foreign), wrapped);
+        assert_eq!(wrapped.convert_endian(foreign)[0].to_bytes(), [0x01, 0x02]);
+    }
+}
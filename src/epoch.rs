@@ -0,0 +1,601 @@
+//! Mission time configuration: the epoch and time scale the spacecraft clock counts in
+//! (cFE's `CFE_MISSION_TIME_EPOCH_*` and `CFE_MISSION_TIME_CFG_DEFAULT_*` settings),
+//! and conversions between spacecraft time, TAI, UTC and GPS time.
+//!
+//! Spacecraft time is the time since the mission epoch, as a [`Duration`];
+//! [`TimeFormat::to_duration`](crate::TimeFormat::to_duration) gets it from a packet timestamp.
+//! UTC is converted to and from the other time scales with a table of leap seconds,
+//! which may be supplied at runtime to keep up with new ones.
+
+use core::fmt;
+use core::time::Duration;
+
+use crate::Error;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// The offset of GPS time from TAI, in seconds: GPS time is TAI &minus; 19 s.
+const GPS_MINUS_TAI: i64 = -19;
+
+/// A time scale a clock may count in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeScale {
+    /// International Atomic Time.
+    Tai,
+
+    /// Coordinated Universal Time, counted without leap seconds
+    /// (as TAI &minus; the current offset from TAI, as cFE does).
+    Utc,
+
+    /// GPS time, TAI &minus; 19 s.
+    Gps,
+}
+
+/// An epoch: a date and time in some time scale that a clock counts from.
+///
+/// The fields follow cFE's `CFE_MISSION_TIME_EPOCH_*` settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Epoch {
+    scale: TimeScale,
+    year: i32,
+    day: u16,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    micros: u32,
+}
+
+impl Epoch {
+    /// cFE's default mission epoch, 1980-001T00:00:00 TAI.
+    pub const CFE_DEFAULT: Self = Self::at_midnight(TimeScale::Tai, 1980, 1);
+
+    /// The CCSDS epoch, 1958-001T00:00:00 TAI, used by "level 1" CCSDS time codes.
+    pub const CCSDS: Self = Self::at_midnight(TimeScale::Tai, 1958, 1);
+
+    /// The GPS epoch, 1980-006T00:00:00 GPS time.
+    pub const GPS: Self = Self::at_midnight(TimeScale::Gps, 1980, 6);
+
+    /// The Unix epoch, 1970-001T00:00:00 UTC.
+    pub const UNIX: Self = Self::at_midnight(TimeScale::Utc, 1970, 1);
+
+    /// Creates an epoch at `year`, day of year `day` (starting from 1),
+    /// `hour`:`minute`:`second` plus `micros` microseconds, in the time scale `scale`.
+    pub const fn new(
+        scale: TimeScale,
+        year: i32,
+        day: u16,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        micros: u32,
+    ) -> Result<Self, Error> {
+        let days_in_year = if is_leap_year(year as i64) { 366 } else { 365 };
+
+        if day == 0
+            || day > days_in_year
+            || hour > 23
+            || minute > 59
+            || second > 59
+            || micros >= 1_000_000
+        {
+            return Err(Error::TimeOutOfRange);
+        }
+
+        Ok(Self {
+            scale,
+            year,
+            day,
+            hour,
+            minute,
+            second,
+            micros,
+        })
+    }
+
+    const fn at_midnight(scale: TimeScale, year: i32, day: u16) -> Self {
+        Self {
+            scale,
+            year,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            micros: 0,
+        }
+    }
+
+    /// Returns the time scale the epoch is given in.
+    pub const fn scale(&self) -> TimeScale {
+        self.scale
+    }
+
+    /// Returns the epoch's date and time as (year, day of year, hour, minute, second, microseconds).
+    pub const fn date_time(&self) -> (i32, u16, u8, u8, u8, u32) {
+        (
+            self.year,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.micros,
+        )
+    }
+
+    /// The epoch as nanoseconds since 1970-01-01T00:00:00 in its own time scale.
+    fn nanos(&self) -> i128 {
+        let days = days_from_civil(self.year as i64, 1, 1) + self.day as i64 - 1;
+        let secs = days * SECS_PER_DAY
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64;
+
+        secs as i128 * NANOS_PER_SEC + self.micros as i128 * 1_000
+    }
+}
+
+/// A change in the offset of TAI from UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LeapSecond {
+    /// The UTC time at which the new offset takes effect, in seconds since the Unix epoch.
+    pub unix_seconds: i64,
+
+    /// TAI &minus; UTC from then on, in seconds.
+    pub tai_minus_utc: i32,
+}
+
+/// A table of leap seconds, in order of time.
+///
+/// Before the first entry, its offset is taken to apply; the table doesn't model
+/// the fractional offsets UTC had before 1972.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeapSeconds<'a> {
+    table: &'a [LeapSecond],
+}
+
+impl<'a> LeapSeconds<'a> {
+    /// The leap seconds announced by the IERS up to the crate's release,
+    /// ending with TAI &minus; UTC = 37 s from 2017-01-01.
+    pub const BUILTIN: LeapSeconds<'static> = LeapSeconds {
+        table: &BUILTIN_LEAP_SECONDS,
+    };
+
+    /// Wraps a table of leap seconds, checking that its entries are in order of time.
+    pub fn new(table: &'a [LeapSecond]) -> Result<Self, Error> {
+        if table
+            .windows(2)
+            .any(|pair| pair[0].unix_seconds >= pair[1].unix_seconds)
+        {
+            return Err(Error::UnsortedLeapSeconds);
+        }
+
+        Ok(Self { table })
+    }
+
+    /// Returns the table's entries.
+    pub fn table(&self) -> &'a [LeapSecond] {
+        self.table
+    }
+
+    /// Returns TAI &minus; UTC, in seconds, at the UTC time `utc` (in nanoseconds since the Unix epoch).
+    fn tai_minus_utc(&self, utc: i128) -> i64 {
+        self.offset_where(|leap| leap.unix_seconds as i128 * NANOS_PER_SEC <= utc)
+    }
+
+    /// Returns TAI &minus; UTC, in seconds, at the TAI time `tai`
+    /// (in nanoseconds since 1970-01-01T00:00:00 TAI).
+    ///
+    /// During a leap second, this is still the offset from before it,
+    /// so UTC times count the leap second as a repeat of the second that follows it.
+    fn tai_minus_utc_at_tai(&self, tai: i128) -> i64 {
+        self.offset_where(|leap| {
+            (leap.unix_seconds + leap.tai_minus_utc as i64) as i128 * NANOS_PER_SEC <= tai
+        })
+    }
+
+    fn offset_where(&self, in_effect: impl Fn(&LeapSecond) -> bool) -> i64 {
+        self.table
+            .iter()
+            .rev()
+            .find(|leap| in_effect(leap))
+            .or(self.table.first())
+            .map_or(0, |leap| leap.tai_minus_utc as i64)
+    }
+}
+
+impl Default for LeapSeconds<'static> {
+    fn default() -> Self {
+        Self::BUILTIN
+    }
+}
+
+/// The mission's time configuration: the epoch the spacecraft clock counts from
+/// (which also gives the time scale it counts in), and the leap seconds to convert to and from UTC with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeConfig<'a> {
+    epoch: Epoch,
+    leap_seconds: LeapSeconds<'a>,
+}
+
+impl<'a> TimeConfig<'a> {
+    /// cFE's default time configuration: a TAI clock counting from 1980-001T00:00:00,
+    /// with the built-in leap second table.
+    pub const CFE_DEFAULT: TimeConfig<'static> = TimeConfig {
+        epoch: Epoch::CFE_DEFAULT,
+        leap_seconds: LeapSeconds::BUILTIN,
+    };
+
+    /// Creates a time configuration for a spacecraft clock counting from `epoch`
+    /// in the epoch's time scale.
+    pub const fn new(epoch: Epoch, leap_seconds: LeapSeconds<'a>) -> Self {
+        Self {
+            epoch,
+            leap_seconds,
+        }
+    }
+
+    /// Returns the mission epoch.
+    pub const fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    /// Returns the leap second table.
+    pub const fn leap_seconds(&self) -> LeapSeconds<'a> {
+        self.leap_seconds
+    }
+
+    /// Converts the spacecraft time `since_epoch` to TAI, as the time since the CCSDS epoch
+    /// (1958-01-01T00:00:00 TAI).
+    pub fn to_tai(&self, since_epoch: Duration) -> Result<Duration, Error> {
+        let tai = self.spacecraft_to_tai(since_epoch);
+        to_duration(tai - Epoch::CCSDS.nanos())
+    }
+
+    /// Converts the spacecraft time `since_epoch` to UTC.
+    ///
+    /// A time during a leap second comes out as the same time one second later.
+    pub fn to_utc(&self, since_epoch: Duration) -> UtcTime {
+        let tai = self.spacecraft_to_tai(since_epoch);
+        UtcTime::from_nanos(
+            tai - self.leap_seconds.tai_minus_utc_at_tai(tai) as i128 * NANOS_PER_SEC,
+        )
+    }
+
+    /// Converts the spacecraft time `since_epoch` to GPS time, as the time since the GPS epoch
+    /// (1980-01-06T00:00:00 GPS time).
+    pub fn to_gps(&self, since_epoch: Duration) -> Result<Duration, Error> {
+        let gps = self.spacecraft_to_tai(since_epoch) + GPS_MINUS_TAI as i128 * NANOS_PER_SEC;
+        to_duration(gps - Epoch::GPS.nanos())
+    }
+
+    /// Converts `tai`, the time since the CCSDS epoch (1958-01-01T00:00:00 TAI),
+    /// to spacecraft time.
+    pub fn from_tai(&self, tai: Duration) -> Result<Duration, Error> {
+        self.tai_to_spacecraft(Epoch::CCSDS.nanos() + tai.as_nanos() as i128)
+    }
+
+    /// Converts the UTC time `utc` to spacecraft time.
+    pub fn from_utc(&self, utc: UtcTime) -> Result<Duration, Error> {
+        let utc = utc.nanos();
+        self.tai_to_spacecraft(utc + self.leap_seconds.tai_minus_utc(utc) as i128 * NANOS_PER_SEC)
+    }
+
+    /// Converts `gps`, the time since the GPS epoch (1980-01-06T00:00:00 GPS time),
+    /// to spacecraft time.
+    pub fn from_gps(&self, gps: Duration) -> Result<Duration, Error> {
+        let gps = Epoch::GPS.nanos() + gps.as_nanos() as i128;
+        self.tai_to_spacecraft(gps - GPS_MINUS_TAI as i128 * NANOS_PER_SEC)
+    }
+
+    /// Converts spacecraft time to nanoseconds since 1970-01-01T00:00:00 TAI.
+    fn spacecraft_to_tai(&self, since_epoch: Duration) -> i128 {
+        let time = self.epoch.nanos() + since_epoch.as_nanos() as i128;
+
+        match self.epoch.scale {
+            TimeScale::Tai => time,
+            TimeScale::Utc => time + self.leap_seconds.tai_minus_utc(time) as i128 * NANOS_PER_SEC,
+            TimeScale::Gps => time - GPS_MINUS_TAI as i128 * NANOS_PER_SEC,
+        }
+    }
+
+    /// Converts nanoseconds since 1970-01-01T00:00:00 TAI to spacecraft time.
+    fn tai_to_spacecraft(&self, tai: i128) -> Result<Duration, Error> {
+        let time = match self.epoch.scale {
+            TimeScale::Tai => tai,
+            TimeScale::Utc => {
+                tai - self.leap_seconds.tai_minus_utc_at_tai(tai) as i128 * NANOS_PER_SEC
+            }
+            TimeScale::Gps => tai + GPS_MINUS_TAI as i128 * NANOS_PER_SEC,
+        };
+
+        to_duration(time - self.epoch.nanos())
+    }
+}
+
+impl Default for TimeConfig<'static> {
+    fn default() -> Self {
+        Self::CFE_DEFAULT
+    }
+}
+
+/// A UTC time, counted without leap seconds from the Unix epoch (1970-01-01T00:00:00 UTC).
+///
+/// Displays as an ISO 8601 date and time, such as `2024-02-29T13:45:30.250000000Z`.
+/// The formatting precision, if given, sets the number of digits of fractional seconds (up to 9).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtcTime {
+    seconds: i64,
+    nanos: u32,
+}
+
+impl UtcTime {
+    /// The Unix epoch, 1970-01-01T00:00:00 UTC.
+    pub const UNIX_EPOCH: Self = Self {
+        seconds: 0,
+        nanos: 0,
+    };
+
+    /// Creates a UTC time `seconds` seconds + `nanoseconds` nanoseconds since the Unix epoch.
+    pub const fn from_unix(seconds: i64, nanoseconds: u32) -> Result<Self, Error> {
+        if nanoseconds >= NANOS_PER_SEC as u32 {
+            return Err(Error::TimeOutOfRange);
+        }
+
+        Ok(Self {
+            seconds,
+            nanos: nanoseconds,
+        })
+    }
+
+    /// Returns the whole seconds since the Unix epoch (negative for earlier times).
+    pub const fn unix_seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// Returns the nanoseconds past [`Self::unix_seconds`].
+    pub const fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Returns the date and time as (year, month, day, hour, minute, second).
+    pub fn date_time(&self) -> (i64, u8, u8, u8, u8, u8) {
+        let days = self.seconds.div_euclid(SECS_PER_DAY);
+        let secs_of_day = self.seconds.rem_euclid(SECS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+
+        (
+            year,
+            month,
+            day,
+            (secs_of_day / 3600) as u8,
+            (secs_of_day / 60 % 60) as u8,
+            (secs_of_day % 60) as u8,
+        )
+    }
+
+    fn from_nanos(nanos: i128) -> Self {
+        Self {
+            seconds: nanos.div_euclid(NANOS_PER_SEC) as i64,
+            nanos: nanos.rem_euclid(NANOS_PER_SEC) as u32,
+        }
+    }
+
+    fn nanos(&self) -> i128 {
+        self.seconds as i128 * NANOS_PER_SEC + self.nanos as i128
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::time::SystemTime> for UtcTime {
+    fn from(time: std::time::SystemTime) -> Self {
+        match time.duration_since(std::time::SystemTime::UNIX_EPOCH) {
+            Ok(since) => Self::from_nanos(since.as_nanos() as i128),
+            Err(before) => Self::from_nanos(-(before.duration().as_nanos() as i128)),
+        }
+    }
+}
+
+impl fmt::Display for UtcTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day, hour, minute, second) = self.date_time();
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}"
+        )?;
+
+        let digits = f.precision().unwrap_or(9).min(9);
+        if digits > 0 {
+            let fraction = self.nanos / 10u32.pow(9 - digits as u32);
+            write!(f, ".{fraction:0digits$}")?;
+        }
+
+        f.write_str("Z")
+    }
+}
+
+fn to_duration(nanos: i128) -> Result<Duration, Error> {
+    if nanos < 0 || nanos / NANOS_PER_SEC > u64::MAX as i128 {
+        return Err(Error::TimeOutOfRange);
+    }
+
+    Ok(Duration::new(
+        (nanos / NANOS_PER_SEC) as u64,
+        (nanos % NANOS_PER_SEC) as u32,
+    ))
+}
+
+const fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Returns the number of days from 1970-01-01 to the given date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the (year, month, day) that's `days` days from 1970-01-01 in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (year, month as u8, day as u8)
+}
+
+const fn leap(unix_seconds: i64, tai_minus_utc: i32) -> LeapSecond {
+    LeapSecond {
+        unix_seconds,
+        tai_minus_utc,
+    }
+}
+
+#[rustfmt::skip]
+const BUILTIN_LEAP_SECONDS: [LeapSecond; 28] = [
+    leap(63_072_000, 10),       // 1972-01-01
+    leap(78_796_800, 11),       // 1972-07-01
+    leap(94_694_400, 12),       // 1973-01-01
+    leap(126_230_400, 13),      // 1974-01-01
+    leap(157_766_400, 14),      // 1975-01-01
+    leap(189_302_400, 15),      // 1976-01-01
+    leap(220_924_800, 16),      // 1977-01-01
+    leap(252_460_800, 17),      // 1978-01-01
+    leap(283_996_800, 18),      // 1979-01-01
+    leap(315_532_800, 19),      // 1980-01-01
+    leap(362_793_600, 20),      // 1981-07-01
+    leap(394_329_600, 21),      // 1982-07-01
+    leap(425_865_600, 22),      // 1983-07-01
+    leap(489_024_000, 23),      // 1985-07-01
+    leap(567_993_600, 24),      // 1988-01-01
+    leap(631_152_000, 25),      // 1990-01-01
+    leap(662_688_000, 26),      // 1991-01-01
+    leap(709_948_800, 27),      // 1992-07-01
+    leap(741_484_800, 28),      // 1993-07-01
+    leap(773_020_800, 29),      // 1994-07-01
+    leap(820_454_400, 30),      // 1996-01-01
+    leap(867_715_200, 31),      // 1997-07-01
+    leap(915_148_800, 32),      // 1999-01-01
+    leap(1_136_073_600, 33),    // 2006-01-01
+    leap(1_230_768_000, 34),    // 2009-01-01
+    leap(1_341_100_800, 35),    // 2012-07-01
+    leap(1_435_708_800, 36),    // 2015-07-01
+    leap(1_483_228_800, 37),    // 2017-01-01
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2017-01-01T00:00:00 UTC, just after the latest leap second, in seconds since the Unix epoch.
+    const NEW_YEAR_2017: i64 = 1_483_228_800;
+
+    /// The same instant as spacecraft time with cFE's default configuration:
+    /// TAI seconds since 1980-01-01T00:00:00 TAI.
+    const NEW_YEAR_2017_CFE: u64 = 1_483_228_800 + 37 - 315_532_800;
+
+    fn utc(unix_seconds: i64) -> UtcTime {
+        UtcTime::from_unix(unix_seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn cfe_epoch_conversions() {
+        let config = TimeConfig::CFE_DEFAULT;
+
+        // the start of 1980 in TAI came before the leap second at the end of 1979 in UTC,
+        // when TAI - UTC was still 18 s
+        let epoch = config.to_utc(Duration::ZERO);
+        assert_eq!(epoch.unix_seconds(), 315_532_800 - 18);
+        assert_eq!(format!("{epoch:.0}"), "1979-12-31T23:59:42Z");
+
+        assert_eq!(
+            config.to_tai(Duration::ZERO),
+            Ok(Duration::from_secs(8_035 * 86_400))
+        );
+
+        // the GPS epoch is 1980-01-06T00:00:00 GPS time, which is 00:00:19 TAI
+        let gps_epoch = Duration::from_secs(5 * 86_400 + 19);
+        assert_eq!(config.to_gps(gps_epoch), Ok(Duration::ZERO));
+        assert_eq!(config.from_gps(Duration::ZERO), Ok(gps_epoch));
+        assert_eq!(config.to_gps(Duration::ZERO), Err(Error::TimeOutOfRange));
+    }
+
+    #[test]
+    fn utc_across_a_leap_second() {
+        let config = TimeConfig::CFE_DEFAULT;
+        let at = |secs: u64| config.to_utc(Duration::from_secs(secs)).unix_seconds();
+
+        assert_eq!(
+            config.from_utc(utc(NEW_YEAR_2017)),
+            Ok(Duration::from_secs(NEW_YEAR_2017_CFE))
+        );
+        assert_eq!(
+            config.from_utc(utc(NEW_YEAR_2017 - 1)),
+            Ok(Duration::from_secs(NEW_YEAR_2017_CFE - 2))
+        );
+
+        assert_eq!(at(NEW_YEAR_2017_CFE - 2), NEW_YEAR_2017 - 1);
+        // 2016-12-31T23:59:60 comes out as a repeat of the second after it
+        assert_eq!(at(NEW_YEAR_2017_CFE - 1), NEW_YEAR_2017);
+        assert_eq!(at(NEW_YEAR_2017_CFE), NEW_YEAR_2017);
+        assert_eq!(at(NEW_YEAR_2017_CFE + 1), NEW_YEAR_2017 + 1);
+    }
+
+    #[test]
+    fn utc_and_gps_clocks() {
+        let unix = TimeConfig::new(Epoch::UNIX, LeapSeconds::BUILTIN);
+        let since_epoch = Duration::from_secs(NEW_YEAR_2017 as u64);
+        assert_eq!(unix.to_utc(since_epoch), utc(NEW_YEAR_2017));
+        assert_eq!(unix.from_utc(utc(NEW_YEAR_2017)), Ok(since_epoch));
+        assert_eq!(
+            unix.to_tai(since_epoch),
+            TimeConfig::CFE_DEFAULT.to_tai(Duration::from_secs(NEW_YEAR_2017_CFE))
+        );
+
+        // GPS time has been 18 s ahead of UTC since 2017
+        let gps = TimeConfig::new(Epoch::GPS, LeapSeconds::BUILTIN);
+        let since_gps_epoch = (NEW_YEAR_2017 - 315_964_800 + 18) as u64;
+        assert_eq!(
+            gps.from_utc(utc(NEW_YEAR_2017)),
+            Ok(Duration::from_secs(since_gps_epoch))
+        );
+        assert_eq!(
+            gps.to_gps(Duration::from_secs(since_gps_epoch)),
+            Ok(Duration::from_secs(since_gps_epoch))
+        );
+    }
+
+    #[test]
+    fn custom_leap_second_tables() {
+        let table = [leap(1_000, 1), leap(2_000, 2)];
+        let leap_seconds = LeapSeconds::new(&table).unwrap();
+        let config = TimeConfig::new(Epoch::UNIX, leap_seconds);
+
+        let tai = |secs| config.to_tai(Duration::from_secs(secs)).unwrap();
+        assert_eq!(config.to_utc(Duration::from_secs(500)), utc(500));
+        // the table's first offset applies before it, too
+        assert_eq!(tai(1_500) - tai(500), Duration::from_secs(1_000));
+        assert_eq!(tai(2_500) - tai(1_500), Duration::from_secs(1_001));
+
+        assert_eq!(
+            LeapSeconds::new(&[leap(2_000, 2), leap(1_000, 1)]),
+            Err(Error::UnsortedLeapSeconds)
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use core::fmt;
+
+/// The ways in which building, modifying, or parsing a packet can fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A byte buffer wasn't the length the packet type requires.
+    WrongLength {
+        /// The length the packet type requires, in bytes.
+        expected: usize,
+        /// The length of the buffer we were handed, in bytes.
+        actual: usize,
+    },
+
+    /// A byte buffer was too short to hold the headers (or the whole packet) it should.
+    Truncated {
+        /// The minimum length required, in bytes.
+        expected: usize,
+        /// The length of the buffer we were handed, in bytes.
+        actual: usize,
+    },
+
+    /// A fixed-capacity buffer is too small to hold the packet.
+    CapacityExceeded {
+        /// The number of bytes the buffer can hold.
+        capacity: usize,
+        /// The number of bytes needed.
+        needed: usize,
+    },
+
+    /// The packet data length field of a primary header
+    /// doesn't agree with the actual length of the packet.
+    LengthFieldMismatch {
+        /// The packet length (in bytes) we actually have.
+        expected: usize,
+        /// The packet length (in bytes) the header claims.
+        actual: usize,
+    },
+
+    /// The payload is too large to be described by the packet data length field.
+    PayloadTooLarge {
+        /// The length of the whole packet, in bytes.
+        len: usize,
+    },
+
+    /// The packet version number doesn't fit in three bits.
+    InvalidVersion(u8),
+
+    /// The application process identifier doesn't fit in 11 bits.
+    InvalidApid(u16),
+
+    /// The packet sequence count doesn't fit in 14 bits.
+    InvalidSequenceCount(u16),
+
+    /// A fixed-capacity sequence counter registry has no room for another APID.
+    TooManyApids {
+        /// The number of APIDs the registry can hold.
+        capacity: usize,
+    },
+
+    /// A segment's sequence count doesn't follow on from the previous segment's,
+    /// so at least one segment is missing.
+    SegmentMissing {
+        /// The segments' APID.
+        apid: u16,
+        /// The sequence count the segment should have had.
+        expected: u16,
+        /// The segment's sequence count.
+        actual: u16,
+    },
+
+    /// A continuation or last segment arrived with no first segment before it.
+    UnexpectedSegment {
+        /// The segment's APID.
+        apid: u16,
+        /// The segment's sequence count.
+        count: u16,
+    },
+
+    /// Segments were asked to carry no user data at all.
+    EmptySegment,
+
+    /// The message ID isn't in the range permitted for this kind of packet.
+    InvalidMsgId(u32),
+
+    /// The command code is larger than the cFS command secondary header allows.
+    InvalidFunctionCode(u16),
+
+    /// The EDS version doesn't fit in five bits.
+    InvalidEdsVersion(u8),
+
+    /// The subsystem ID doesn't fit in nine bits.
+    InvalidSubsystem(u16),
+
+    /// A time is out of the range a time code can represent,
+    /// or a time code field is out of its permitted range.
+    TimeOutOfRange,
+
+    /// A time code's P-field doesn't describe the expected kind of time code.
+    /// Contains the first byte of the P-field.
+    InvalidPField(u8),
+
+    /// A table of leap seconds isn't in order of time.
+    UnsortedLeapSeconds,
+
+    /// The packet version number or packet type bits are wrong for this kind of packet.
+    /// Contains the first byte of the primary header.
+    InvalidVersionOrType(u8),
+
+    /// The secondary header flag doesn't agree with whether the packet has a secondary header.
+    /// Contains the first byte of the primary header.
+    InvalidSecondaryHeaderFlag(u8),
+
+    /// The sequence flags aren't `0b11` (unsegmented).
+    /// Contains the third byte of the primary header.
+    InvalidSequenceFlags(u8),
+
+    /// The reserved bit in the cFS command secondary header is set.
+    /// Contains the byte holding the command code and the reserved bit.
+    ReservedBitSet(u8),
+
+    /// The PUS acknowledgement flags don't fit in 4 bits.
+    InvalidAckFlags(u8),
+
+    /// The PUS spacecraft time reference status doesn't fit in 4 bits.
+    InvalidTimeReferenceStatus(u8),
+
+    /// The PUS version number in the secondary header isn't that of PUS-C.
+    InvalidPusVersion(u8),
+
+    /// The checksum in the cFS command secondary header doesn't match the packet's contents.
+    ChecksumMismatch {
+        /// The checksum computed from the packet's contents.
+        expected: u8,
+        /// The checksum found in the packet.
+        actual: u8,
+    },
+
+    /// The CRC in the packet error control field doesn't match the packet's contents.
+    PecMismatch {
+        /// The CRC computed from the packet's contents.
+        expected: u16,
+        /// The CRC found in the packet.
+        actual: u16,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::WrongLength { expected, actual } => {
+                write!(f, "packet is {actual} bytes long, expected {expected}")
+            }
+            Error::Truncated { expected, actual } => {
+                write!(
+                    f,
+                    "buffer is {actual} bytes long, at least {expected} needed"
+                )
+            }
+            Error::CapacityExceeded { capacity, needed } => {
+                write!(f, "buffer holds {capacity} bytes, {needed} needed")
+            }
+            Error::LengthFieldMismatch { expected, actual } => write!(
+                f,
+                "packet length field implies {actual} bytes, but packet is {expected} bytes long"
+            ),
+            Error::PayloadTooLarge { len } => {
+                write!(
+                    f,
+                    "packet length of {len} bytes is too large for a CCSDS packet"
+                )
+            }
+            Error::InvalidVersion(version) => write!(f, "invalid packet version number {version}"),
+            Error::InvalidApid(apid) => write!(f, "invalid APID {apid:#05X}"),
+            Error::InvalidSequenceCount(count) => write!(f, "invalid sequence count {count}"),
+            Error::TooManyApids { capacity } => {
+                write!(f, "sequence counters full, with {capacity} APIDs")
+            }
+            Error::SegmentMissing {
+                apid,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "segment missing for APID {apid:#05X}: sequence count is {actual}, expected {expected}"
+            ),
+            Error::UnexpectedSegment { apid, count } => write!(
+                f,
+                "segment {count} for APID {apid:#05X} has no first segment"
+            ),
+            Error::EmptySegment => write!(f, "segments must carry at least one byte of user data"),
+            Error::InvalidMsgId(msg_id) => write!(f, "invalid message ID {msg_id:#06X}"),
+            Error::InvalidFunctionCode(code) => write!(f, "invalid command code {code:#X}"),
+            Error::InvalidEdsVersion(version) => write!(f, "invalid EDS version {version}"),
+            Error::InvalidSubsystem(subsystem) => {
+                write!(f, "invalid subsystem ID {subsystem:#05X}")
+            }
+            Error::TimeOutOfRange => write!(f, "time out of range for time code"),
+            Error::InvalidPField(byte) => write!(f, "invalid time code P-field {byte:#04X}"),
+            Error::UnsortedLeapSeconds => write!(f, "leap second table is not in order of time"),
+            Error::InvalidVersionOrType(byte) => {
+                write!(
+                    f,
+                    "invalid packet version or type in header byte {byte:#04X}"
+                )
+            }
+            Error::InvalidSecondaryHeaderFlag(byte) => {
+                write!(f, "invalid secondary header flag in header byte {byte:#04X}")
+            }
+            Error::InvalidSequenceFlags(byte) => {
+                write!(f, "invalid sequence flags in header byte {byte:#04X}")
+            }
+            Error::ReservedBitSet(byte) => {
+                write!(f, "reserved bit set in command header byte {byte:#04X}")
+            }
+            Error::InvalidAckFlags(flags) => write!(f, "invalid acknowledgement flags {flags:#04X}"),
+            Error::InvalidTimeReferenceStatus(status) => {
+                write!(f, "invalid time reference status {status:#04X}")
+            }
+            Error::InvalidPusVersion(version) => write!(f, "invalid PUS version number {version}"),
+            Error::ChecksumMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "command checksum is {actual:#04X}, expected {expected:#04X}"
+                )
+            }
+            Error::PecMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "packet error control CRC is {actual:#06X}, expected {expected:#06X}"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
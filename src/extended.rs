@@ -0,0 +1,458 @@
+//! cFS packets with the cFE extended header between the primary header and the cFS secondary header,
+//! as cFE builds them when configured with `MESSAGE_FORMAT_IS_CCSDS_VER_2`.
+
+use core::marker::PhantomData;
+
+use crate::{
+    cfe_checksum, check_checksum, check_primary_header, expected_checksum, AnyBitPattern, Cfe32_16,
+    CommandMsgId, Error, ExtendedHeader, MsgIdScheme, MsgIdV2, NoPadding, PacketType,
+    PrimaryHeader, SecondaryHeader, SequenceFlags, SpacePacket, TelemetryMsgId, TimeConfig,
+    TimeFormat, TimeSource, UtcTime, MAX_FUNCTION_CODE,
+};
+
+/// The length of the primary and extended headers together, in bytes.
+const HEADERS_LEN: usize = PrimaryHeader::LEN + ExtendedHeader::LEN;
+
+/// The length of the headers of a cFS command with an extended header, in bytes.
+const EXT_COMMAND_HEADER_LEN: usize = HEADERS_LEN + 2;
+
+/// The cFE extended header followed by the cFS command secondary header (the command code and the checksum),
+/// for a command whose message ID is mapped onto the headers by `M`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CfsExtCommandHeader<M: MsgIdScheme> {
+    extended: ExtendedHeader,
+    bytes: [u8; EXT_COMMAND_HEADER_LEN - HEADERS_LEN],
+    msg_ids: PhantomData<M>,
+}
+
+// Safety: CfsExtCommandHeader is repr(C), and its non-zero-sized fields are byte arrays
+// (or wrap one), with no padding between them.
+unsafe impl<M: MsgIdScheme> AnyBitPattern for CfsExtCommandHeader<M> {}
+unsafe impl<M: MsgIdScheme> NoPadding for CfsExtCommandHeader<M> {}
+
+impl<M: MsgIdScheme> SecondaryHeader for CfsExtCommandHeader<M> {
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_primary_header::<M>(bytes, bytes.len(), PacketType::Command)?;
+        if bytes[HEADERS_LEN] & 0x80 != 0x00 {
+            return Err(Error::ReservedBitSet(bytes[HEADERS_LEN]));
+        }
+
+        Ok(())
+    }
+}
+
+impl<M: MsgIdScheme> CfsExtCommandHeader<M> {
+    /// Returns the cFE extended header.
+    pub fn extended_header(&self) -> &ExtendedHeader {
+        &self.extended
+    }
+
+    /// Returns the command code.
+    pub fn function_code(&self) -> u16 {
+        self.bytes[0] as u16
+    }
+
+    /// Returns the contents of the checksum field.
+    pub fn checksum(&self) -> u8 {
+        self.bytes[1]
+    }
+}
+
+/// The cFE extended header followed by the cFS telemetry secondary header
+/// (the timestamp in the time format `F`, plus any structure padding),
+/// for a telemetry message whose message ID is mapped onto the headers by `M`.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct CfsExtTelemetryHeader<M: MsgIdScheme, F: TimeFormat> {
+    extended: ExtendedHeader,
+    time: F::Header,
+    msg_ids: PhantomData<M>,
+    time_format: PhantomData<F>,
+}
+
+// Safety: CfsExtTelemetryHeader is packed, so there's no padding,
+// and its non-zero-sized fields are AnyBitPattern and NoPadding.
+unsafe impl<M: MsgIdScheme, F: TimeFormat> AnyBitPattern for CfsExtTelemetryHeader<M, F> {}
+unsafe impl<M: MsgIdScheme, F: TimeFormat> NoPadding for CfsExtTelemetryHeader<M, F> {}
+
+impl<M: MsgIdScheme, F: TimeFormat> SecondaryHeader for CfsExtTelemetryHeader<M, F> {
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_primary_header::<M>(bytes, bytes.len(), PacketType::Telemetry)
+    }
+}
+
+impl<M: MsgIdScheme, F: TimeFormat> CfsExtTelemetryHeader<M, F> {
+    /// Returns the cFE extended header.
+    pub fn extended_header(&self) -> &ExtendedHeader {
+        &self.extended
+    }
+
+    /// Returns the timestamp, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        // the time field may be unaligned, so work on a copy
+        let time = self.time;
+        F::timestamp(time.as_ref())
+    }
+
+    /// Sets the timestamp to `timestamp`, given in the form used by the time format `F`.
+    fn set_timestamp_to(&mut self, timestamp: F::Timestamp) {
+        let mut time = self.time;
+        F::set_timestamp(time.as_mut(), timestamp);
+        self.time = time;
+    }
+}
+
+/// A cFS-flavor CCSDS command packet with a cFE extended header, as a Rust structure,
+/// whose message ID is mapped onto the headers by `M`.
+pub type ExtCommandPacket<T, M> = SpacePacket<CfsExtCommandHeader<M>, T>;
+
+/// A cFS-flavor CCSDS telemetry packet with a cFE extended header, as a Rust structure,
+/// whose message ID is mapped onto the headers by `M` and whose timestamp is in the time format `F`.
+pub type ExtTelemetryPacket<T, M, F> = SpacePacket<CfsExtTelemetryHeader<M, F>, T>;
+
+/// A cFS-flavor CCSDS command packet with a cFE extended header, using cFE's extended-header message ID mapping.
+pub type ExtCommand<T> = ExtCommandPacket<T, MsgIdV2>;
+
+/// A cFS-flavor CCSDS telemetry packet with a cFE extended header,
+/// using cFE's extended-header message ID mapping and default packet time format.
+pub type ExtTelemetry<T> = ExtTelemetryPacket<T, MsgIdV2, Cfe32_16>;
+
+impl<T: Copy, M: MsgIdScheme> ExtCommandPacket<T, M> {
+    /// If `function_code` is a permissible command code,
+    /// returns a new `ExtCommand` with the payload initialized to `payload`; otherwise returns an error.
+    ///
+    /// Extended header fields other than those encoding the message ID start out zeroed.
+    pub fn new(msg_id: CommandMsgId<M>, function_code: u16, payload: T) -> Result<Self, Error> {
+        // check that fields are in their allowed ranges
+        if function_code > MAX_FUNCTION_CODE {
+            return Err(Error::InvalidFunctionCode(function_code));
+        }
+
+        let (primary, extended) = cfs_headers::<M>(msg_id.get(), Self::LEN)?;
+
+        // cFS secondary header for commands: command code and optional checksum
+        let secondary = CfsExtCommandHeader {
+            extended,
+            bytes: [function_code as u8, 0x00],
+            msg_ids: PhantomData,
+        };
+
+        Ok(Self::new_unchecked(primary, secondary, payload))
+    }
+
+    /// [`Self::new`], but using [`Default::default`]`()` as the payload.
+    pub fn new_default(msg_id: CommandMsgId<M>, function_code: u16) -> Result<Self, Error>
+    where
+        T: Default,
+    {
+        Self::new(msg_id, function_code, Default::default())
+    }
+
+    /// [`Self::new`], but with the checksum field set to a valid checksum
+    /// (see [`Self::set_checksum`]) rather than left as zero.
+    pub fn new_with_checksum(
+        msg_id: CommandMsgId<M>,
+        function_code: u16,
+        payload: T,
+    ) -> Result<Self, Error>
+    where
+        T: NoPadding,
+    {
+        let mut cmd = Self::new(msg_id, function_code, payload)?;
+        cmd.set_checksum();
+        Ok(cmd)
+    }
+
+    /// Updates the checksum field (see [`Self::set_checksum`]),
+    /// then returns a view of the `ExtCommand` as a sequence of bytes, ready for transmission.
+    pub fn as_bytes_with_checksum(&mut self) -> &[u8]
+    where
+        T: NoPadding,
+    {
+        self.set_checksum();
+        self.as_bytes()
+    }
+
+    /// Returns the message's cFE extended header.
+    pub fn extended_header(&self) -> &ExtendedHeader {
+        self.secondary_header().extended_header()
+    }
+
+    /// Returns the message's cFE extended header for modification.
+    ///
+    /// Note that with [`MsgIdV2`], the subsystem ID is part of the message ID.
+    pub fn extended_header_mut(&mut self) -> &mut ExtendedHeader {
+        &mut self.secondary_header_mut().extended
+    }
+
+    /// Returns the message's message ID.
+    pub fn msg_id(&self) -> CommandMsgId<M> {
+        CommandMsgId::new_unchecked(M::msg_id(&headers(
+            self.primary_header(),
+            self.extended_header(),
+        )))
+    }
+
+    /// Returns the message's command code.
+    pub fn function_code(&self) -> u16 {
+        self.secondary_header().function_code()
+    }
+
+    /// Returns the contents of the message's checksum field.
+    pub fn checksum(&self) -> u8 {
+        self.secondary_header().checksum()
+    }
+
+    /// Computes the checksum the message's checksum field should contain,
+    /// given the rest of the message's contents.
+    pub fn compute_checksum(&self) -> u8
+    where
+        T: NoPadding,
+    {
+        expected_checksum(self.as_bytes(), self.checksum())
+    }
+
+    /// Sets the message's checksum field to the checksum of the rest of the message,
+    /// as cFE's `CFE_MSG_GenerateChecksum` does.
+    ///
+    /// The checksum is not kept up to date automatically:
+    /// call this again after modifying the headers or payload.
+    pub fn set_checksum(&mut self)
+    where
+        T: NoPadding,
+    {
+        self.secondary_header_mut().bytes[1] = self.compute_checksum();
+    }
+
+    /// Returns whether the message's checksum field is consistent with its contents,
+    /// as cFE's `CFE_MSG_ValidateChecksum` would determine.
+    pub fn is_checksum_valid(&self) -> bool
+    where
+        T: NoPadding,
+    {
+        cfe_checksum(self.as_bytes()) == 0
+    }
+
+    /// Returns an error describing the discrepancy if the message's checksum field is inconsistent with its contents.
+    pub fn validate_checksum(&self) -> Result<(), Error>
+    where
+        T: NoPadding,
+    {
+        check_checksum(self.as_bytes(), self.checksum())
+    }
+
+    /// Sets the message's message ID to `msg_id`.
+    pub fn set_msg_id(&mut self, msg_id: CommandMsgId<M>) {
+        let mut primary = *self.primary_header();
+        set_msg_id::<M>(&mut primary, self.extended_header_mut(), msg_id.get());
+        *self.primary_header_mut() = primary;
+    }
+
+    /// If `function_code` is a valid command code, sets the message's function code to `function_code`.
+    pub fn set_function_code(&mut self, function_code: u16) -> Result<(), Error> {
+        if function_code <= MAX_FUNCTION_CODE {
+            self.secondary_header_mut().bytes[0] = function_code as u8;
+            Ok(())
+        } else {
+            Err(Error::InvalidFunctionCode(function_code))
+        }
+    }
+}
+
+impl<T: Copy, M: MsgIdScheme, F: TimeFormat> ExtTelemetryPacket<T, M, F> {
+    /// Returns a new `ExtTelemetry` with the payload initialized to `payload`,
+    /// or an error if the payload is too large to fit in a packet.
+    ///
+    /// Extended header fields other than those encoding the message ID start out zeroed.
+    pub fn new(msg_id: TelemetryMsgId<M>, payload: T) -> Result<Self, Error> {
+        let (primary, extended) = cfs_headers::<M>(msg_id.get(), Self::LEN)?;
+
+        // cFS secondary header for telemetry (timestamp and any structure padding) starts zeroed
+        let secondary = CfsExtTelemetryHeader {
+            extended,
+            time: F::Header::default(),
+            msg_ids: PhantomData,
+            time_format: PhantomData,
+        };
+
+        Ok(Self::new_unchecked(primary, secondary, payload))
+    }
+
+    /// [`Self::new`], but using [`Default::default`]`()` as the payload.
+    pub fn new_default(msg_id: TelemetryMsgId<M>) -> Result<Self, Error>
+    where
+        T: Default,
+    {
+        Self::new(msg_id, Default::default())
+    }
+
+    /// Returns the message's cFE extended header.
+    pub fn extended_header(&self) -> &ExtendedHeader {
+        self.secondary_header().extended_header()
+    }
+
+    /// Returns the message's cFE extended header for modification.
+    ///
+    /// Note that with [`MsgIdV2`], the subsystem ID is part of the message ID.
+    pub fn extended_header_mut(&mut self) -> &mut ExtendedHeader {
+        &mut self.secondary_header_mut().extended
+    }
+
+    /// Returns the message's message ID.
+    pub fn msg_id(&self) -> TelemetryMsgId<M> {
+        TelemetryMsgId::new_unchecked(M::msg_id(&headers(
+            self.primary_header(),
+            self.extended_header(),
+        )))
+    }
+
+    /// Returns the message's timestamp, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        self.secondary_header().timestamp()
+    }
+
+    /// Returns the message's timestamp as a UTC time,
+    /// taking the spacecraft clock to be configured as in `config`.
+    pub fn timestamp_utc(&self, config: &TimeConfig) -> UtcTime {
+        config.to_utc(F::to_duration(self.timestamp()))
+    }
+
+    /// Sets the message's message ID to `msg_id`.
+    pub fn set_msg_id(&mut self, msg_id: TelemetryMsgId<M>) {
+        let mut primary = *self.primary_header();
+        set_msg_id::<M>(&mut primary, self.extended_header_mut(), msg_id.get());
+        *self.primary_header_mut() = primary;
+    }
+
+    /// Sets the message's timestamp to
+    /// `seconds` seconds + `nanoseconds` nanoseconds
+    /// since the flight-software epoch, rounded down to the resolution of the time format `F`.
+    pub fn set_timestamp(&mut self, seconds: u64, nanoseconds: u32) {
+        self.set_timestamp_to(F::from_secs_nanos(seconds, nanoseconds));
+    }
+
+    /// Sets the message's timestamp to `timestamp`, given in the form used by the time format `F`.
+    pub fn set_timestamp_to(&mut self, timestamp: F::Timestamp) {
+        self.secondary_header_mut().set_timestamp_to(timestamp);
+    }
+
+    /// Sets the message's timestamp to the current time according to `source`.
+    pub fn timestamp_with<S: TimeSource + ?Sized>(&mut self, source: &S) -> Result<(), Error> {
+        let since_epoch = source.now()?;
+
+        self.set_timestamp(since_epoch.as_secs(), since_epoch.subsec_nanos());
+        Ok(())
+    }
+
+    /// Sets the message's timestamp to the current time, counted in UTC seconds
+    /// (without leap seconds) since 1980-01-01T00:00:00 UTC.
+    ///
+    /// Fails with [`Error::TimeOutOfRange`] if the host's clock reads earlier than the epoch.
+    /// Use [`Self::timestamp_with_now_in`] for a spacecraft clock with another epoch or time scale.
+    #[cfg(feature = "std")]
+    pub fn timestamp_with_now(&mut self) -> Result<(), Error> {
+        self.set_timestamp_to(crate::system_time_now::<F>()?);
+        Ok(())
+    }
+
+    /// Sets the message's timestamp to the current time,
+    /// for a spacecraft clock configured as in `config`.
+    #[cfg(feature = "std")]
+    pub fn timestamp_with_now_in(&mut self, config: &TimeConfig) -> Result<(), Error> {
+        self.timestamp_with(&crate::SystemTimeSource::new(*config))
+    }
+}
+
+/// Builds the primary and extended headers of an unsegmented cFS packet with the message ID `msg_id`
+/// (which should already have been checked) that's `packet_len` bytes long in total.
+fn cfs_headers<M: MsgIdScheme>(
+    msg_id: u32,
+    packet_len: usize,
+) -> Result<(PrimaryHeader, ExtendedHeader), Error> {
+    let mut primary = PrimaryHeader::default();
+    let mut extended = ExtendedHeader::default();
+    primary.set_secondary_header_flag(true);
+    set_msg_id::<M>(&mut primary, &mut extended, msg_id);
+
+    primary.set_sequence_flags(SequenceFlags::Unsegmented);
+    primary.set_packet_len(packet_len)?;
+    Ok((primary, extended))
+}
+
+/// Returns the primary and extended headers as one run of bytes, as [`MsgIdScheme`] expects them.
+fn headers(primary: &PrimaryHeader, extended: &ExtendedHeader) -> [u8; HEADERS_LEN] {
+    let mut headers = [0; HEADERS_LEN];
+    headers[..PrimaryHeader::LEN].copy_from_slice(primary.as_bytes());
+    headers[PrimaryHeader::LEN..].copy_from_slice(extended.as_bytes());
+    headers
+}
+
+/// Encodes `msg_id` (which should already have been checked) into the primary and extended headers.
+fn set_msg_id<M: MsgIdScheme>(
+    primary: &mut PrimaryHeader,
+    extended: &mut ExtendedHeader,
+    msg_id: u32,
+) {
+    let mut bytes = headers(primary, extended);
+    M::set_msg_id(&mut bytes, msg_id);
+    *primary = PrimaryHeader::from_bytes(bytes[..PrimaryHeader::LEN].try_into().unwrap());
+    *extended = ExtendedHeader::from_bytes(bytes[PrimaryHeader::LEN..].try_into().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ext_command_known_answer() {
+        let mut cmd = ExtCommand::new(CommandMsgId::new(0x1A85), 4, [0xAAu8, 0xBB]).unwrap();
+        cmd.extended_header_mut().set_eds_version(3).unwrap();
+        cmd.extended_header_mut().set_system(0xBEEF);
+
+        assert_eq!(cmd.compute_checksum(), 0x63);
+        let bytes = cmd.as_bytes_with_checksum().to_vec();
+        assert_eq!(
+            bytes,
+            [0x18, 0x05, 0xC0, 0x00, 0x00, 0x07, 0x18, 0x1A, 0xBE, 0xEF, 0x04, 0x63, 0xAA, 0xBB]
+        );
+
+        let parsed = ExtCommand::<[u8; 2]>::from_bytes(&bytes).unwrap();
+        assert!(parsed.is_checksum_valid());
+        assert_eq!(parsed.validate_checksum(), Ok(()));
+        assert_eq!(parsed.msg_id(), CommandMsgId::new(0x1A85));
+        assert_eq!(parsed.function_code(), 4);
+        assert_eq!(parsed.extended_header().eds_version(), 3);
+        assert_eq!(parsed.extended_header().system(), 0xBEEF);
+
+        let mut corrupted = parsed.clone();
+        corrupted.payload[0] = 0xAB;
+        assert_eq!(
+            corrupted.validate_checksum(),
+            Err(Error::ChecksumMismatch {
+                expected: 0x62,
+                actual: 0x63
+            })
+        );
+    }
+
+    #[test]
+    fn ext_telemetry_known_answer() {
+        let mut tlm = ExtTelemetry::new(TelemetryMsgId::new(0x1A05), [1u8, 2]).unwrap();
+        tlm.set_timestamp_to((0x0102_0304, 0x0506));
+        tlm.extended_header_mut().set_playback(true);
+
+        assert_eq!(
+            tlm.as_bytes(),
+            [
+                0x08, 0x05, 0xC0, 0x00, 0x00, 0x0F, 0x02, 0x1A, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04,
+                0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02
+            ]
+        );
+
+        let parsed = ExtTelemetry::<[u8; 2]>::from_bytes(tlm.as_bytes()).unwrap();
+        assert_eq!(parsed.msg_id(), TelemetryMsgId::new(0x1A05));
+        assert_eq!(parsed.timestamp(), (0x0102_0304, 0x0506));
+        assert!(parsed.extended_header().is_playback());
+    }
+}
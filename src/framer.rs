@@ -0,0 +1,409 @@
+//! Carving packets out of a continuous byte stream, such as a serial or TCP link.
+
+use crate::raw::primary_header;
+use crate::{PacketBuffer, PacketType, PrimaryHeader, RawPacket};
+
+/// The length of the longest possible space packet, in bytes.
+const MAX_PACKET_LEN: usize = PrimaryHeader::LEN + 0x10000;
+
+/// An incremental decoder that splits a byte stream, fed to it in chunks of any size,
+/// into space packets, using the packet data length field.
+///
+/// Each packet must start with a plausible primary header: one with a version number of 0,
+/// a length no greater than the framer's maximum packet length,
+/// the packet type set with [`Self::with_packet_type`] (if any),
+/// and passing the header check, if one's been set with [`Self::with_header_check`].
+/// Where the stream doesn't start with one (say, after bytes were corrupted or lost),
+/// the framer resynchronizes by discarding bytes until it does.
+/// As packets follow one another back to back, after resynchronizing a header is only taken at its word
+/// if the bytes after the end of its packet start another header
+/// with a version number of 0 and a length no greater than the maximum,
+/// which keeps the framer from locking onto garbage that happens to look like a header;
+/// the framer waits for that next header to arrive before handing out the packet.
+/// While in sync, a packet is handed out as soon as it's complete, whatever follows it.
+/// Idle packets are handed out like any other unless [`Self::skip_idle`] is used.
+///
+/// The framer's bytes are kept in a [`PacketBuffer`]; a fixed-capacity buffer
+/// such as [`ArrayBuffer`](crate::ArrayBuffer) also limits the packet length.
+///
+/// Feed and drain the framer in turns:
+///
+/// ```text
+/// let mut chunk = received;
+/// while !chunk.is_empty() {
+///     let taken = framer.push(chunk);
+///     chunk = &chunk[taken..];
+///     while let Some(packet) = framer.next_packet() {
+///         handle(packet);
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PacketFramer<B: PacketBuffer> {
+    buffer: B,
+
+    /// The number of bytes at the start of the buffer that have already been handed out or discarded.
+    consumed: usize,
+
+    max_packet_len: usize,
+    packet_type: Option<PacketType>,
+    header_check: Option<fn(&PrimaryHeader) -> bool>,
+    skip_idle: bool,
+
+    /// Whether the start of the buffer is known to be the start of a packet,
+    /// rather than somewhere the framer has resynchronized to.
+    synced: bool,
+
+    skipped: u64,
+    idle: u64,
+}
+
+impl<B: PacketBuffer> PacketFramer<B> {
+    /// Creates a framer keeping its bytes in `buffer`, which is emptied first.
+    pub fn new_in(mut buffer: B) -> Self {
+        // shrinking a buffer never fails
+        let _ = buffer.resize(0);
+        let max_packet_len = buffer.max_len().unwrap_or(MAX_PACKET_LEN);
+
+        Self {
+            buffer,
+            consumed: 0,
+            max_packet_len: max_packet_len.min(MAX_PACKET_LEN),
+            packet_type: None,
+            header_check: None,
+            skip_idle: false,
+            synced: true,
+            skipped: 0,
+            idle: 0,
+        }
+    }
+
+    /// Limits packets to `max_packet_len` bytes (or the buffer's capacity, if that's less);
+    /// a header claiming a longer packet is taken to be corrupt.
+    pub fn with_max_packet_len(mut self, max_packet_len: usize) -> Self {
+        let limit = self.buffer.max_len().unwrap_or(MAX_PACKET_LEN);
+        self.max_packet_len = max_packet_len.min(limit).min(MAX_PACKET_LEN);
+        self
+    }
+
+    /// Only accepts packets of type `packet_type`, such as telemetry on a downlink;
+    /// headers of the other type are taken to be corrupt.
+    pub fn with_packet_type(mut self, packet_type: PacketType) -> Self {
+        self.packet_type = Some(packet_type);
+        self
+    }
+
+    /// Only accepts packets whose primary header passes `check`
+    /// (say, by having an APID the mission uses); other headers are taken to be corrupt.
+    pub fn with_header_check(mut self, check: fn(&PrimaryHeader) -> bool) -> Self {
+        self.header_check = Some(check);
+        self
+    }
+
+    /// Discards idle packets instead of handing them out.
+    pub fn skip_idle(mut self) -> Self {
+        self.skip_idle = true;
+        self
+    }
+
+    /// Appends as much of `bytes` to the framer's buffer as it can hold,
+    /// returning the number of bytes taken.
+    ///
+    /// With a fixed-capacity buffer, that may be less than `bytes.len()`;
+    /// take the complete packets out with [`Self::next_packet`], then push the rest.
+    pub fn push(&mut self, bytes: &[u8]) -> usize {
+        self.compact();
+
+        let len = self.buffer.as_slice().len();
+        let room = match self.buffer.max_len() {
+            Some(max_len) => max_len - len,
+            None => bytes.len(),
+        };
+        let taken = bytes.len().min(room);
+
+        if self.buffer.resize(len + taken).is_err() {
+            return 0;
+        }
+        self.buffer.as_mut_slice()[len..].copy_from_slice(&bytes[..taken]);
+        taken
+    }
+
+    /// Returns the next complete packet in the stream, if there is one yet,
+    /// discarding any bytes before it that don't start a plausible packet
+    /// (and any idle packets before it, if they're being skipped).
+    pub fn next_packet(&mut self) -> Option<RawPacket<'_>> {
+        self.compact();
+
+        let bytes = self.buffer.as_slice();
+        let mut start = 0;
+        while bytes.len() - start >= PrimaryHeader::LEN {
+            let header = primary_header(&bytes[start..]);
+            if !self.is_plausible(header) {
+                // resynchronize, one byte at a time
+                start += 1;
+                self.skipped += 1;
+                self.synced = false;
+                continue;
+            }
+
+            let packet_len = header.packet_len();
+            if bytes.len() - start < packet_len {
+                break;
+            }
+
+            // a real packet is followed by the header of the next one;
+            // after losing sync, wait for that header before trusting this one
+            // (unless the buffer's too small to hold them both)
+            if !self.synced {
+                let next = &bytes[start + packet_len..];
+                if next.len() >= PrimaryHeader::LEN {
+                    if !self.is_well_formed(primary_header(next)) {
+                        start += 1;
+                        self.skipped += 1;
+                        continue;
+                    }
+                } else if self
+                    .buffer
+                    .max_len()
+                    .map_or(true, |max_len| packet_len + PrimaryHeader::LEN <= max_len)
+                {
+                    break;
+                }
+                self.synced = true;
+            }
+
+            if self.skip_idle && header.is_idle() {
+                start += packet_len;
+                self.idle += 1;
+                continue;
+            }
+
+            self.consumed = start + packet_len;
+            return Some(RawPacket::new_unchecked(&bytes[start..start + packet_len]));
+        }
+
+        self.consumed = start;
+        None
+    }
+
+    /// Returns the total number of bytes discarded while resynchronizing.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns the total number of idle packets discarded.
+    pub fn skipped_idle_packets(&self) -> u64 {
+        self.idle
+    }
+
+    /// Returns the number of bytes buffered that haven't been handed out as packets yet.
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.as_slice().len() - self.consumed
+    }
+
+    /// Discards any buffered bytes, say, after the link has been reset.
+    pub fn clear(&mut self) {
+        self.consumed = 0;
+        self.synced = true;
+        let _ = self.buffer.resize(0);
+    }
+
+    /// Whether `header` could start a packet at all, whether or not it's one the framer accepts.
+    fn is_well_formed(&self, header: &PrimaryHeader) -> bool {
+        header.version() == 0 && header.packet_len() <= self.max_packet_len
+    }
+
+    fn is_plausible(&self, header: &PrimaryHeader) -> bool {
+        self.is_well_formed(header)
+            && self
+                .packet_type
+                .map_or(true, |packet_type| header.packet_type() == packet_type)
+            && self.header_check.map_or(true, |check| check(header))
+    }
+
+    /// Drops the bytes that have already been handed out or discarded from the front of the buffer.
+    fn compact(&mut self) {
+        if self.consumed == 0 {
+            return;
+        }
+
+        let len = self.buffer.as_slice().len();
+        self.buffer.as_mut_slice().copy_within(self.consumed.., 0);
+        // shrinking a buffer never fails
+        let _ = self.buffer.resize(len - self.consumed);
+        self.consumed = 0;
+    }
+}
+
+#[cfg(feature = "std")]
+impl PacketFramer<Vec<u8>> {
+    /// Creates a framer keeping its bytes in a `Vec` that grows as needed.
+    pub fn new() -> Self {
+        Self::new_in(Vec::new())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for PacketFramer<Vec<u8>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ArrayBuffer;
+
+    fn packet(apid: u16, count: u16, data: &[u8]) -> Vec<u8> {
+        let mut header = PrimaryHeader::default();
+        header.set_apid(apid).unwrap();
+        header.set_sequence_count(count).unwrap();
+        header
+            .set_packet_len(PrimaryHeader::LEN + data.len())
+            .unwrap();
+
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    /// Feeds `stream` to `framer` in chunks of `chunk_len` bytes, returning the packets framed.
+    fn frame<B: PacketBuffer>(
+        framer: &mut PacketFramer<B>,
+        stream: &[u8],
+        chunk_len: usize,
+    ) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        for mut chunk in stream.chunks(chunk_len) {
+            while !chunk.is_empty() {
+                let taken = framer.push(chunk);
+                chunk = &chunk[taken..];
+                while let Some(packet) = framer.next_packet() {
+                    packets.push(packet.as_bytes().to_vec());
+                }
+            }
+        }
+        packets
+    }
+
+    #[test]
+    fn frames_packets_in_any_chunks() {
+        let packets = [
+            packet(0x10, 0, b"one"),
+            packet(0x10, 1, &[0; 100]),
+            packet(0x11, 0, b"x"),
+        ];
+        let stream = packets.concat();
+
+        for chunk_len in 1..=stream.len() {
+            let mut framer = PacketFramer::new_in(ArrayBuffer::<256>::new());
+            assert_eq!(frame(&mut framer, &stream, chunk_len), packets);
+            assert_eq!(framer.skipped_bytes(), 0);
+            assert_eq!(framer.buffered_len(), 0);
+        }
+    }
+
+    #[test]
+    fn resynchronizes_past_garbage_and_false_headers() {
+        let packets = [
+            packet(0x20, 0, b"first"),
+            packet(0x20, 1, b"second"),
+            packet(0x20, 2, b"third"),
+        ];
+        let mut stream = vec![0xFF, 0xFF, 0xFF];
+        // a plausible header claiming a packet that isn't followed by another header
+        stream.extend_from_slice(&[0x00, 0x20, 0xC0, 0x00, 0x00, 0x05]);
+        stream.extend_from_slice(&packets.concat());
+
+        for chunk_len in 1..=stream.len() {
+            let mut framer =
+                PacketFramer::new_in(ArrayBuffer::<256>::new()).with_max_packet_len(32);
+            assert_eq!(
+                frame(&mut framer, &stream, chunk_len),
+                packets,
+                "chunks of {chunk_len}"
+            );
+            assert_eq!(framer.skipped_bytes(), 9);
+        }
+    }
+
+    #[test]
+    fn hands_out_packets_followed_by_garbage() {
+        let packets = [
+            packet(0x21, 0, b"before"),
+            packet(0x21, 1, b"after"),
+            packet(0x21, 2, b"last"),
+        ];
+        let mut stream = packets[0].clone();
+        stream.extend_from_slice(&[0xFF; 8]);
+        stream.extend_from_slice(&packets[1..].concat());
+
+        for split in 0..=stream.len() {
+            let mut framer = PacketFramer::new_in(ArrayBuffer::<256>::new());
+            let mut framed = frame(&mut framer, &stream[..split], stream.len());
+            framed.extend(frame(&mut framer, &stream[split..], stream.len()));
+            assert_eq!(framed, packets, "split at {split}");
+            assert_eq!(framer.skipped_bytes(), 8);
+        }
+    }
+
+    #[test]
+    fn skips_idle_packets_only_when_asked() {
+        let mut idle = [0; 10];
+        crate::IdleGenerator::new().write(&mut idle).unwrap();
+        let packets = [packet(0x22, 0, b"one"), packet(0x22, 1, b"two")];
+        let stream = [&idle, &packets[0][..], &idle, &idle, &packets[1][..], &idle].concat();
+
+        for chunk_len in 1..=stream.len() {
+            let mut framer = PacketFramer::new_in(ArrayBuffer::<256>::new()).skip_idle();
+            assert_eq!(frame(&mut framer, &stream, chunk_len), packets);
+            assert_eq!(framer.skipped_idle_packets(), 4);
+            assert_eq!(framer.skipped_bytes(), 0);
+        }
+
+        let mut framer = PacketFramer::new_in(ArrayBuffer::<256>::new());
+        let framed = frame(&mut framer, &stream, stream.len());
+        assert_eq!(framed.len(), 6);
+        assert_eq!(framed[2], idle);
+        assert_eq!(framer.skipped_idle_packets(), 0);
+    }
+
+    #[test]
+    fn rejects_implausible_headers() {
+        let telemetry = packet(0x30, 0, b"tlm");
+        let mut command = packet(0x30, 0, b"cmd");
+        command[0] |= 0x10;
+        let long = packet(0x30, 1, &[0xEE; 70]);
+        let stream = [
+            telemetry.clone(),
+            command,
+            long,
+            telemetry.clone(),
+            telemetry.clone(),
+        ]
+        .concat();
+
+        let mut framer = PacketFramer::new_in(ArrayBuffer::<256>::new())
+            .with_packet_type(PacketType::Telemetry)
+            .with_max_packet_len(32);
+        // the telemetry packet after the resynchronization waits for the one after it
+        assert_eq!(
+            frame(&mut framer, &stream, stream.len()),
+            [telemetry.clone(), telemetry.clone(), telemetry]
+        );
+        assert_eq!(framer.skipped_bytes(), 9 + 76);
+    }
+
+    #[test]
+    fn confirms_packets_too_long_to_look_past() {
+        let packets = [packet(0x40, 0, &[0; 10]), packet(0x40, 1, &[0; 10])];
+        let mut stream = vec![0xFF];
+        stream.extend_from_slice(&packets.concat());
+
+        // the buffer can't hold a packet and the next header together
+        let mut framer = PacketFramer::new_in(ArrayBuffer::<20>::new());
+        assert_eq!(frame(&mut framer, &stream, 1), packets);
+        assert_eq!(framer.skipped_bytes(), 1);
+    }
+}
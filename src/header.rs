@@ -0,0 +1,526 @@
+use core::fmt;
+
+use crate::{AnyBitPattern, Endianness, Error, NoPadding};
+
+/// The kind of a CCSDS packet, as given by the packet type bit of the primary header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PacketType {
+    /// A telemetry (or reporting) packet.
+    Telemetry = 0,
+    /// A telecommand (or requesting) packet.
+    Command = 1,
+}
+
+/// The sequence flags of a CCSDS packet, describing where it falls in a segmented piece of user data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SequenceFlags {
+    /// A segment in the middle of the user data.
+    Continuation = 0b00,
+    /// The first segment of the user data.
+    First = 0b01,
+    /// The last segment of the user data.
+    Last = 0b10,
+    /// The packet contains the user data in its entirety.
+    Unsegmented = 0b11,
+}
+
+impl SequenceFlags {
+    /// Turns the two-bit value of the sequence flags field into a `SequenceFlags`.
+    /// Only the low two bits of `bits` are used.
+    pub const fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => SequenceFlags::Continuation,
+            0b01 => SequenceFlags::First,
+            0b10 => SequenceFlags::Last,
+            _ => SequenceFlags::Unsegmented,
+        }
+    }
+}
+
+/// A CCSDS space packet primary header, stored in its on-the-wire representation.
+///
+/// All fields are decoded from (and encoded into) the six header bytes on demand,
+/// so a `PrimaryHeader` has the same size and alignment as `[u8; 6]`.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PrimaryHeader([u8; 6]);
+
+impl PrimaryHeader {
+    /// The length of a primary header, in bytes.
+    pub const LEN: usize = 6;
+
+    /// The largest permissible application process identifier.
+    pub const MAX_APID: u16 = 0x7FF;
+
+    /// The APID reserved for idle packets, which carry no user data and only fill link capacity.
+    pub const IDLE_APID: u16 = 0x7FF;
+
+    /// The largest permissible packet sequence count.
+    pub const MAX_SEQUENCE_COUNT: u16 = 0x3FFF;
+
+    /// Wraps the six bytes of a primary header.
+    pub const fn from_bytes(bytes: [u8; 6]) -> Self {
+        Self(bytes)
+    }
+
+    /// Reinterprets a reference to six header bytes as a reference to a `PrimaryHeader`.
+    pub fn from_bytes_ref(bytes: &[u8; 6]) -> &Self {
+        // Safety: PrimaryHeader is a repr(transparent) wrapper around [u8; 6].
+        unsafe { &*(bytes as *const [u8; 6] as *const Self) }
+    }
+
+    /// Reinterprets a mutable reference to six header bytes as a mutable reference to a `PrimaryHeader`.
+    pub fn from_bytes_mut(bytes: &mut [u8; 6]) -> &mut Self {
+        // Safety: PrimaryHeader is a repr(transparent) wrapper around [u8; 6].
+        unsafe { &mut *(bytes as *mut [u8; 6] as *mut Self) }
+    }
+
+    /// Returns the six bytes of the primary header.
+    pub const fn to_bytes(self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Returns a view of the primary header as a sequence of bytes.
+    pub const fn as_bytes(&self) -> &[u8; 6] {
+        &self.0
+    }
+
+    /// Returns a view of the primary header as a sequence of bytes, for modification.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8; 6] {
+        &mut self.0
+    }
+
+    /// Returns the packet version number. For version 1 CCSDS packets, this is `0`.
+    pub const fn version(&self) -> u8 {
+        self.0[0] >> 5
+    }
+
+    /// If `version` fits in three bits, sets the packet version number to `version`.
+    pub fn set_version(&mut self, version: u8) -> Result<(), Error> {
+        if version > 0b111 {
+            return Err(Error::InvalidVersion(version));
+        }
+        self.0[0] = (self.0[0] & 0x1F) | (version << 5);
+        Ok(())
+    }
+
+    /// Returns the packet type.
+    pub const fn packet_type(&self) -> PacketType {
+        if self.0[0] & 0x10 != 0 {
+            PacketType::Command
+        } else {
+            PacketType::Telemetry
+        }
+    }
+
+    /// Sets the packet type.
+    pub fn set_packet_type(&mut self, packet_type: PacketType) {
+        self.0[0] = (self.0[0] & !0x10) | ((packet_type as u8) << 4);
+    }
+
+    /// Returns whether the secondary header flag is set.
+    pub const fn has_secondary_header(&self) -> bool {
+        self.0[0] & 0x08 != 0
+    }
+
+    /// Sets or clears the secondary header flag.
+    pub fn set_secondary_header_flag(&mut self, flag: bool) {
+        self.0[0] = (self.0[0] & !0x08) | ((flag as u8) << 3);
+    }
+
+    /// Returns the 11-bit application process identifier.
+    pub const fn apid(&self) -> u16 {
+        (((self.0[0] & 0x07) as u16) << 8) | (self.0[1] as u16)
+    }
+
+    /// If `apid` fits in 11 bits, sets the application process identifier to `apid`.
+    pub fn set_apid(&mut self, apid: u16) -> Result<(), Error> {
+        if apid > Self::MAX_APID {
+            return Err(Error::InvalidApid(apid));
+        }
+        self.0[0] = (self.0[0] & 0xF8) | (apid >> 8) as u8;
+        self.0[1] = apid as u8;
+        Ok(())
+    }
+
+    /// Returns whether this is the header of an idle packet, i.e., whether its APID is [`Self::IDLE_APID`].
+    pub const fn is_idle(&self) -> bool {
+        self.apid() == Self::IDLE_APID
+    }
+
+    /// Returns the first 16 bits of the header (the packet version number and the packet identification),
+    /// which cFS calls the stream ID.
+    pub const fn stream_id(&self) -> u16 {
+        ((self.0[0] as u16) << 8) | (self.0[1] as u16)
+    }
+
+    /// Sets the first 16 bits of the header (the packet version number and the packet identification).
+    pub fn set_stream_id(&mut self, stream_id: u16) {
+        self.0[0] = (stream_id >> 8) as u8;
+        self.0[1] = stream_id as u8;
+    }
+
+    /// Returns the sequence flags.
+    pub const fn sequence_flags(&self) -> SequenceFlags {
+        SequenceFlags::from_bits(self.0[2] >> 6)
+    }
+
+    /// Sets the sequence flags.
+    pub fn set_sequence_flags(&mut self, flags: SequenceFlags) {
+        self.0[2] = (self.0[2] & 0x3F) | ((flags as u8) << 6);
+    }
+
+    /// Returns the 14-bit packet sequence count.
+    pub const fn sequence_count(&self) -> u16 {
+        (((self.0[2] & 0x3F) as u16) << 8) | (self.0[3] as u16)
+    }
+
+    /// If `count` fits in 14 bits, sets the packet sequence count to `count`.
+    pub fn set_sequence_count(&mut self, count: u16) -> Result<(), Error> {
+        if count > Self::MAX_SEQUENCE_COUNT {
+            return Err(Error::InvalidSequenceCount(count));
+        }
+        self.0[2] = (self.0[2] & 0xC0) | (count >> 8) as u8;
+        self.0[3] = count as u8;
+        Ok(())
+    }
+
+    /// Increments the packet sequence count, wrapping around to zero after [`Self::MAX_SEQUENCE_COUNT`].
+    pub fn increment_sequence_count(&mut self) {
+        let count = self.sequence_count().wrapping_add(1) & Self::MAX_SEQUENCE_COUNT;
+        self.0[2] = (self.0[2] & 0xC0) | (count >> 8) as u8;
+        self.0[3] = count as u8;
+    }
+
+    /// Returns the raw packet data length field,
+    /// which is one less than the number of bytes following the primary header.
+    pub const fn data_length(&self) -> u16 {
+        ((self.0[4] as u16) << 8) | (self.0[5] as u16)
+    }
+
+    /// Sets the raw packet data length field.
+    pub fn set_data_length(&mut self, data_length: u16) {
+        self.0[4] = (data_length >> 8) as u8;
+        self.0[5] = data_length as u8;
+    }
+
+    /// Returns the length of the whole packet (primary header included) implied by the data length field.
+    pub const fn packet_len(&self) -> usize {
+        self.data_length() as usize + Self::LEN + 1
+    }
+
+    /// If a packet `packet_len` bytes long (primary header included) can be described by the
+    /// data length field, sets the data length field accordingly.
+    pub fn set_packet_len(&mut self, packet_len: usize) -> Result<(), Error> {
+        match packet_len.checked_sub(Self::LEN + 1) {
+            Some(data_length) if data_length <= 0xFFFF => {
+                self.set_data_length(data_length as u16);
+                Ok(())
+            }
+            Some(_) => Err(Error::PayloadTooLarge { len: packet_len }),
+            None => Err(Error::WrongLength {
+                expected: Self::LEN + 1,
+                actual: packet_len,
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for PrimaryHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrimaryHeader")
+            .field("version", &self.version())
+            .field("packet_type", &self.packet_type())
+            .field("secondary_header", &self.has_secondary_header())
+            .field("apid", &self.apid())
+            .field("sequence_flags", &self.sequence_flags())
+            .field("sequence_count", &self.sequence_count())
+            .field("data_length", &self.data_length())
+            .finish()
+    }
+}
+
+/// A cFE extended header (the APID qualifiers), stored in its on-the-wire representation.
+///
+/// cFE adds this header directly after the primary header when built with `MESSAGE_FORMAT_IS_CCSDS_VER_2`.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ExtendedHeader([u8; 4]);
+
+// Safety: an ExtendedHeader is just four bytes, any of which may have any value.
+unsafe impl AnyBitPattern for ExtendedHeader {}
+unsafe impl NoPadding for ExtendedHeader {}
+
+impl ExtendedHeader {
+    /// The length of an extended header, in bytes.
+    pub const LEN: usize = 4;
+
+    /// The largest permissible EDS version.
+    pub const MAX_EDS_VERSION: u8 = 0x1F;
+
+    /// The largest permissible subsystem ID.
+    pub const MAX_SUBSYSTEM: u16 = 0x1FF;
+
+    /// Wraps the four bytes of an extended header.
+    pub const fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self(bytes)
+    }
+
+    /// Reinterprets a reference to four header bytes as a reference to an `ExtendedHeader`.
+    pub fn from_bytes_ref(bytes: &[u8; 4]) -> &Self {
+        // Safety: ExtendedHeader is a repr(transparent) wrapper around [u8; 4].
+        unsafe { &*(bytes as *const [u8; 4] as *const Self) }
+    }
+
+    /// Reinterprets a mutable reference to four header bytes as a mutable reference to an `ExtendedHeader`.
+    pub fn from_bytes_mut(bytes: &mut [u8; 4]) -> &mut Self {
+        // Safety: ExtendedHeader is a repr(transparent) wrapper around [u8; 4].
+        unsafe { &mut *(bytes as *mut [u8; 4] as *mut Self) }
+    }
+
+    /// Returns the four bytes of the extended header.
+    pub const fn to_bytes(self) -> [u8; 4] {
+        self.0
+    }
+
+    /// Returns a view of the extended header as a sequence of bytes.
+    pub const fn as_bytes(&self) -> &[u8; 4] {
+        &self.0
+    }
+
+    /// Returns a view of the extended header as a sequence of bytes, for modification.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8; 4] {
+        &mut self.0
+    }
+
+    /// Returns the 5-bit EDS version.
+    pub const fn eds_version(&self) -> u8 {
+        self.0[0] >> 3
+    }
+
+    /// If `version` fits in five bits, sets the EDS version to `version`.
+    pub fn set_eds_version(&mut self, version: u8) -> Result<(), Error> {
+        if version > Self::MAX_EDS_VERSION {
+            return Err(Error::InvalidEdsVersion(version));
+        }
+        self.0[0] = (self.0[0] & 0x07) | (version << 3);
+        Ok(())
+    }
+
+    /// Returns the byte order the endian flag says the payload is in.
+    pub const fn endianness(&self) -> Endianness {
+        if self.0[0] & 0x04 != 0 {
+            Endianness::Little
+        } else {
+            Endianness::Big
+        }
+    }
+
+    /// Sets the endian flag to indicate that the payload is in byte order `order`.
+    pub fn set_endianness(&mut self, order: Endianness) {
+        let flag = match order {
+            Endianness::Big => 0x00,
+            Endianness::Little => 0x04,
+        };
+        self.0[0] = (self.0[0] & !0x04) | flag;
+    }
+
+    /// Returns whether the playback flag is set (that is, whether this is recorded rather than original data).
+    pub const fn is_playback(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+
+    /// Sets or clears the playback flag.
+    pub fn set_playback(&mut self, playback: bool) {
+        self.0[0] = (self.0[0] & !0x02) | ((playback as u8) << 1);
+    }
+
+    /// Returns the 9-bit subsystem ID.
+    pub const fn subsystem(&self) -> u16 {
+        (((self.0[0] & 0x01) as u16) << 8) | (self.0[1] as u16)
+    }
+
+    /// If `subsystem` fits in nine bits, sets the subsystem ID to `subsystem`.
+    pub fn set_subsystem(&mut self, subsystem: u16) -> Result<(), Error> {
+        if subsystem > Self::MAX_SUBSYSTEM {
+            return Err(Error::InvalidSubsystem(subsystem));
+        }
+        self.0[0] = (self.0[0] & !0x01) | (subsystem >> 8) as u8;
+        self.0[1] = subsystem as u8;
+        Ok(())
+    }
+
+    /// Returns the 16-bit system ID.
+    pub const fn system(&self) -> u16 {
+        ((self.0[2] as u16) << 8) | (self.0[3] as u16)
+    }
+
+    /// Sets the system ID.
+    pub fn set_system(&mut self, system: u16) {
+        self.0[2] = (system >> 8) as u8;
+        self.0[3] = system as u8;
+    }
+}
+
+impl fmt::Debug for ExtendedHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedHeader")
+            .field("eds_version", &self.eds_version())
+            .field("endianness", &self.endianness())
+            .field("playback", &self.is_playback())
+            .field("subsystem", &self.subsystem())
+            .field("system", &self.system())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primary_header_bit_layout() {
+        let mut header = PrimaryHeader::default();
+
+        header.set_version(0b101).unwrap();
+        assert_eq!(header.to_bytes(), [0xA0, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        header.set_packet_type(PacketType::Command);
+        assert_eq!(header.to_bytes(), [0xB0, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        header.set_secondary_header_flag(true);
+        assert_eq!(header.to_bytes(), [0xB8, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        header.set_apid(0x5A5).unwrap();
+        assert_eq!(header.to_bytes(), [0xBD, 0xA5, 0x00, 0x00, 0x00, 0x00]);
+        header.set_sequence_flags(SequenceFlags::Last);
+        assert_eq!(header.to_bytes(), [0xBD, 0xA5, 0x80, 0x00, 0x00, 0x00]);
+        header.set_sequence_count(0x2A5A).unwrap();
+        assert_eq!(header.to_bytes(), [0xBD, 0xA5, 0xAA, 0x5A, 0x00, 0x00]);
+        header.set_data_length(0x1234);
+        assert_eq!(header.to_bytes(), [0xBD, 0xA5, 0xAA, 0x5A, 0x12, 0x34]);
+
+        assert_eq!(header.version(), 0b101);
+        assert_eq!(header.packet_type(), PacketType::Command);
+        assert!(header.has_secondary_header());
+        assert_eq!(header.apid(), 0x5A5);
+        assert_eq!(header.stream_id(), 0xBDA5);
+        assert_eq!(header.sequence_flags(), SequenceFlags::Last);
+        assert_eq!(header.sequence_count(), 0x2A5A);
+        assert_eq!(header.data_length(), 0x1234);
+        assert_eq!(header.packet_len(), 0x1234 + 7);
+    }
+
+    #[test]
+    fn primary_header_fields_stay_in_their_bits() {
+        let all_ones = PrimaryHeader::from_bytes([0xFF; 6]);
+        assert_eq!(all_ones.version(), 0b111);
+        assert_eq!(all_ones.packet_type(), PacketType::Command);
+        assert!(all_ones.has_secondary_header());
+        assert_eq!(all_ones.apid(), 0x7FF);
+        assert!(all_ones.is_idle());
+        assert_eq!(all_ones.sequence_flags(), SequenceFlags::Unsegmented);
+        assert_eq!(all_ones.sequence_count(), 0x3FFF);
+        assert_eq!(all_ones.packet_len(), 65542);
+
+        // clearing one field leaves its neighbours alone
+        let mut header = all_ones;
+        header.set_version(0).unwrap();
+        header.set_apid(0).unwrap();
+        header.set_sequence_count(0).unwrap();
+        assert_eq!(header.to_bytes(), [0x18, 0x00, 0xC0, 0x00, 0xFF, 0xFF]);
+        header.set_packet_type(PacketType::Telemetry);
+        header.set_secondary_header_flag(false);
+        header.set_sequence_flags(SequenceFlags::Continuation);
+        assert_eq!(header.to_bytes(), [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF]);
+
+        // out-of-range values are turned away rather than spilling into other fields
+        let mut header = PrimaryHeader::default();
+        assert_eq!(
+            header.set_version(0b1000),
+            Err(Error::InvalidVersion(0b1000))
+        );
+        assert_eq!(header.set_apid(0x800), Err(Error::InvalidApid(0x800)));
+        assert_eq!(
+            header.set_sequence_count(0x4000),
+            Err(Error::InvalidSequenceCount(0x4000))
+        );
+        assert_eq!(header.to_bytes(), [0; 6]);
+        assert_eq!(SequenceFlags::from_bits(0b110), SequenceFlags::Last);
+
+        // incrementing the count wraps around without touching the sequence flags
+        let mut header = PrimaryHeader::from_bytes([0x00, 0x00, 0x7F, 0xFF, 0x00, 0x00]);
+        header.increment_sequence_count();
+        assert_eq!(header.to_bytes(), [0x00, 0x00, 0x40, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn packet_len_bounds() {
+        let mut header = PrimaryHeader::default();
+
+        header.set_packet_len(7).unwrap();
+        assert_eq!(header.data_length(), 0);
+        header.set_packet_len(65542).unwrap();
+        assert_eq!(header.data_length(), 0xFFFF);
+        assert_eq!(header.packet_len(), 65542);
+
+        assert_eq!(
+            header.set_packet_len(6),
+            Err(Error::WrongLength {
+                expected: 7,
+                actual: 6
+            })
+        );
+        assert_eq!(
+            header.set_packet_len(0),
+            Err(Error::WrongLength {
+                expected: 7,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            header.set_packet_len(65543),
+            Err(Error::PayloadTooLarge { len: 65543 })
+        );
+        assert_eq!(header.data_length(), 0xFFFF);
+    }
+
+    #[test]
+    fn extended_header_bit_layout() {
+        let mut header = ExtendedHeader::from_bytes([0; 4]);
+
+        header.set_eds_version(0x1F).unwrap();
+        assert_eq!(header.to_bytes(), [0xF8, 0x00, 0x00, 0x00]);
+        header.set_endianness(Endianness::Little);
+        assert_eq!(header.to_bytes(), [0xFC, 0x00, 0x00, 0x00]);
+        header.set_playback(true);
+        assert_eq!(header.to_bytes(), [0xFE, 0x00, 0x00, 0x00]);
+        header.set_subsystem(0x1FF).unwrap();
+        assert_eq!(header.to_bytes(), [0xFF, 0xFF, 0x00, 0x00]);
+        header.set_system(0x1234);
+        assert_eq!(header.to_bytes(), [0xFF, 0xFF, 0x12, 0x34]);
+
+        assert_eq!(header.eds_version(), 0x1F);
+        assert_eq!(header.endianness(), Endianness::Little);
+        assert!(header.is_playback());
+        assert_eq!(header.subsystem(), 0x1FF);
+        assert_eq!(header.system(), 0x1234);
+
+        // clearing each field leaves the others alone
+        header.set_eds_version(0).unwrap();
+        assert_eq!(header.to_bytes(), [0x07, 0xFF, 0x12, 0x34]);
+        header.set_endianness(Endianness::Big);
+        assert_eq!(header.to_bytes(), [0x03, 0xFF, 0x12, 0x34]);
+        header.set_playback(false);
+        assert_eq!(header.to_bytes(), [0x01, 0xFF, 0x12, 0x34]);
+        header.set_subsystem(0x0A5).unwrap();
+        assert_eq!(header.to_bytes(), [0x00, 0xA5, 0x12, 0x34]);
+
+        assert_eq!(
+            header.set_eds_version(0x20),
+            Err(Error::InvalidEdsVersion(0x20))
+        );
+        assert_eq!(
+            header.set_subsystem(0x200),
+            Err(Error::InvalidSubsystem(0x200))
+        );
+        assert_eq!(header.to_bytes(), [0x00, 0xA5, 0x12, 0x34]);
+    }
+}
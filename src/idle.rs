@@ -0,0 +1,196 @@
+//! Making idle packets, which carry no user data and are sent only to keep a link's frames full.
+
+use crate::{Error, PacketType, PrimaryHeader, SequenceFlags};
+
+/// What to fill the packet data field of an idle packet with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IdleFill<'a> {
+    /// The same byte throughout.
+    Byte(u8),
+
+    /// The bytes 0x00, 0x01, ..., 0xFF, over and over.
+    #[default]
+    Counting,
+
+    /// The given bytes, over and over; an empty pattern fills with zeros.
+    Pattern(&'a [u8]),
+}
+
+impl IdleFill<'_> {
+    /// Fills `data` with the pattern, starting from its beginning.
+    fn fill(&self, data: &mut [u8]) {
+        match *self {
+            IdleFill::Byte(byte) => data.fill(byte),
+            IdleFill::Counting => {
+                for (i, byte) in data.iter_mut().enumerate() {
+                    *byte = i as u8;
+                }
+            }
+            IdleFill::Pattern([]) => data.fill(0),
+            IdleFill::Pattern(pattern) => {
+                for (byte, &fill) in data.iter_mut().zip(pattern.iter().cycle()) {
+                    *byte = fill;
+                }
+            }
+        }
+    }
+}
+
+/// A source of idle packets of any length, each with the next sequence count for the idle APID.
+///
+/// Idle packets are telemetry packets without a secondary header by default,
+/// with their packet data field filled by [`IdleFill::Counting`].
+#[derive(Clone, Debug)]
+pub struct IdleGenerator<'a> {
+    /// The header of the next idle packet, less its length.
+    header: PrimaryHeader,
+    fill: IdleFill<'a>,
+}
+
+impl<'a> IdleGenerator<'a> {
+    /// The length of the shortest possible idle packet, in bytes.
+    pub const MIN_PACKET_LEN: usize = PrimaryHeader::LEN + 1;
+
+    /// The length of the longest possible idle packet, in bytes.
+    pub const MAX_PACKET_LEN: usize = PrimaryHeader::LEN + 0x10000;
+
+    /// Creates a generator whose first idle packet has a sequence count of 0.
+    pub fn new() -> Self {
+        let mut header = PrimaryHeader::default();
+        // the idle APID is in range, so this can't fail
+        header.set_apid(PrimaryHeader::IDLE_APID).unwrap();
+        header.set_sequence_flags(SequenceFlags::Unsegmented);
+
+        Self {
+            header,
+            fill: IdleFill::default(),
+        }
+    }
+
+    /// Fills idle packets according to `fill`.
+    pub fn with_fill(mut self, fill: IdleFill<'a>) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Makes idle packets of type `packet_type`.
+    pub fn with_packet_type(mut self, packet_type: PacketType) -> Self {
+        self.header.set_packet_type(packet_type);
+        self
+    }
+
+    /// If `count` fits in 14 bits, starts the sequence counts of the idle packets from `count`.
+    pub fn with_sequence_count(mut self, count: u16) -> Result<Self, Error> {
+        self.header.set_sequence_count(count)?;
+        Ok(self)
+    }
+
+    /// Returns the sequence count the next idle packet will get.
+    pub fn next_sequence_count(&self) -> u16 {
+        self.header.sequence_count()
+    }
+
+    /// Writes an idle packet filling the whole of `out`.
+    ///
+    /// Fails if `out` isn't between [`Self::MIN_PACKET_LEN`] and [`Self::MAX_PACKET_LEN`] bytes long.
+    pub fn write(&mut self, out: &mut [u8]) -> Result<(), Error> {
+        let mut header = self.header;
+        header.set_packet_len(out.len())?;
+
+        let (header_bytes, data) = out.split_at_mut(PrimaryHeader::LEN);
+        header_bytes.copy_from_slice(header.as_bytes());
+        self.fill.fill(data);
+
+        self.header.increment_sequence_count();
+        Ok(())
+    }
+
+    /// Returns an idle packet `packet_len` bytes long.
+    ///
+    /// Fails if `packet_len` isn't between [`Self::MIN_PACKET_LEN`] and [`Self::MAX_PACKET_LEN`].
+    #[cfg(feature = "std")]
+    pub fn to_vec(&mut self, packet_len: usize) -> Result<Vec<u8>, Error> {
+        PrimaryHeader::default().set_packet_len(packet_len)?;
+
+        let mut packet = vec![0; packet_len];
+        self.write(&mut packet)?;
+        Ok(packet)
+    }
+}
+
+impl Default for IdleGenerator<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_packets_use_the_idle_apid() {
+        let mut idle = IdleGenerator::new();
+        let mut out = [0xAA; 12];
+        idle.write(&mut out).unwrap();
+
+        assert_eq!(
+            out,
+            [0x07, 0xFF, 0xC0, 0x00, 0x00, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05]
+        );
+        let header = PrimaryHeader::from_bytes_ref(out[..6].try_into().unwrap());
+        assert!(header.is_idle());
+        assert_eq!(header.packet_len(), 12);
+        assert_eq!(header.packet_type(), PacketType::Telemetry);
+
+        assert_eq!(
+            idle.write(&mut [0; PrimaryHeader::LEN]),
+            Err(Error::WrongLength {
+                expected: 7,
+                actual: 6
+            })
+        );
+        // a failed write doesn't use up a sequence count
+        assert_eq!(idle.next_sequence_count(), 1);
+    }
+
+    #[test]
+    fn idle_sequence_counts_wrap_around() {
+        let mut idle = IdleGenerator::new().with_sequence_count(0x3FFE).unwrap();
+        let mut out = [0; 7];
+
+        for expected in [0x3FFE, 0x3FFF, 0, 1] {
+            idle.write(&mut out).unwrap();
+            let header = PrimaryHeader::from_bytes_ref(out[..6].try_into().unwrap());
+            assert_eq!(header.sequence_count(), expected);
+        }
+        assert_eq!(idle.next_sequence_count(), 2);
+
+        assert!(IdleGenerator::new().with_sequence_count(0x4000).is_err());
+    }
+
+    #[test]
+    fn idle_fills() {
+        let mut out = [0; 11];
+
+        IdleGenerator::new()
+            .with_fill(IdleFill::Byte(0x55))
+            .write(&mut out)
+            .unwrap();
+        assert_eq!(out[6..], [0x55; 5]);
+
+        IdleGenerator::new()
+            .with_fill(IdleFill::Pattern(&[1, 2]))
+            .write(&mut out)
+            .unwrap();
+        assert_eq!(out[6..], [1, 2, 1, 2, 1]);
+
+        IdleGenerator::new()
+            .with_fill(IdleFill::Pattern(&[]))
+            .with_packet_type(PacketType::Command)
+            .write(&mut out)
+            .unwrap();
+        assert_eq!(out[..1], [0x17]);
+        assert_eq!(out[6..], [0; 5]);
+    }
+}
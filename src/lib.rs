@@ -1,9 +1,72 @@
-#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+use core::marker::PhantomData;
+
+use time::telemetry_header_len;
+
+mod clock;
+mod dynamic;
+mod endian;
+mod epoch;
+mod error;
+mod extended;
+mod framer;
+mod header;
+mod idle;
+mod msg_id;
+mod packet;
+mod pod;
+mod pus;
+mod raw;
+mod segment;
+mod sequence;
+mod time;
+mod time_code;
+mod view;
 
-#[cfg(not(target_endian = "little"))]
-core::compile_error!("This crate is only supported on little-endian systems.");
-
-use core::mem::size_of;
+#[cfg(feature = "std")]
+pub use clock::SystemTimeSource;
+pub use clock::{ManualClock, TimeSource};
+pub use dynamic::{
+    ArrayBuffer, DynCommand, DynCommandPacket, DynTelemetry, DynTelemetryPacket, PacketBuffer,
+    SliceBuffer,
+};
+pub use endian::{
+    ConvertEndian, Endianness, F32Be, F32Le, F64Be, F64Le, I128Be, I128Le, I16Be, I16Le, I32Be,
+    I32Le, I64Be, I64Le, U128Be, U128Le, U16Be, U16Le, U32Be, U32Le, U64Be, U64Le,
+};
+pub use epoch::{Epoch, LeapSecond, LeapSeconds, TimeConfig, TimeScale, UtcTime};
+pub use error::Error;
+pub use extended::{ExtCommand, ExtCommandPacket, ExtTelemetry, ExtTelemetryPacket};
+pub use framer::PacketFramer;
+pub use header::{ExtendedHeader, PacketType, PrimaryHeader, SequenceFlags};
+pub use idle::{IdleFill, IdleGenerator};
+pub use msg_id::{CommandMsgId, MsgIdRange, MsgIdScheme, MsgIdV1, MsgIdV2, TelemetryMsgId};
+pub use packet::{SecondaryHeader, SpacePacket};
+pub use pod::{AnyBitPattern, FieldInfo, NoPadding, Payload};
+pub use pus::{crc16_ccitt, PusTc, PusTcHeader, PusTm, PusTmHeader, PusTmPacket, WithPec};
+pub use raw::{PacketIter, RawPacket};
+#[cfg(feature = "std")]
+pub use segment::{Reassembled, Reassembler};
+pub use segment::{Segment, Segmenter};
+#[cfg(target_has_atomic = "16")]
+pub use sequence::AtomicSequenceCounters;
+pub use sequence::{
+    ApidTracker, SequenceCounterArray, SequenceCounters, SequenceEvent, SequenceStats,
+};
+#[cfg(feature = "std")]
+pub use sequence::{SequenceCounterMap, SequenceTracker};
+pub use time::{Cfe32Micro, Cfe32_16, Cfe32_16Packed, Cfe32_32, TimeFormat};
+pub use time_code::{Cds, Cuc, TimeCodeEpoch};
+pub use view::{
+    CommandMut, CommandRef, CommandView, CommandViewMut, TelemetryMut, TelemetryRef, TelemetryView,
+    TelemetryViewMut,
+};
+
+/// Derives [`AnyBitPattern`], [`NoPadding`], [`ConvertEndian`] and [`Payload`] for a `#[repr(C)]` payload struct,
+/// checking at compile time that it has no padding.
+#[cfg(feature = "derive")]
+pub use ccsds_packet_derive::Payload;
 
 #[cfg(feature = "std")]
 use std::time::Duration;
@@ -13,291 +76,410 @@ use std::time::Duration;
 #[cfg(feature = "std")]
 const FLIGHT_SOFTWARE_EPOCH: Duration = Duration::new(315532800, 0); // 1980-01-01T00:00:00 UTC
 
-/// A cFS-flavor CCSDS command packet, as a Rust structure.
+/// The length of the headers of a cFS command, in bytes.
+const COMMAND_HEADER_LEN: usize = 8;
+
+const MAX_FUNCTION_CODE: u16 = 0x7F;
+
+/// The cFS command secondary header: the command code and the checksum,
+/// for a command whose message ID is mapped onto the headers by `M`.
 #[repr(C)]
-#[derive(Clone)]
-pub struct Command<T: Copy> {
-    /// The command header.
-    header: [u8; 8],
-
-    /// The message's payload. As messages are copied
-    /// willy-nilly, `T` needs to be [`Copy`].
-    pub payload: T,
+#[derive(Clone, Copy, Debug)]
+pub struct CfsCommandHeader<M: MsgIdScheme> {
+    bytes: [u8; COMMAND_HEADER_LEN - PrimaryHeader::LEN],
+    msg_ids: PhantomData<M>,
+}
+
+// Safety: CfsCommandHeader is repr(C), and its only non-zero-sized field is a byte array.
+unsafe impl<M: MsgIdScheme> AnyBitPattern for CfsCommandHeader<M> {}
+unsafe impl<M: MsgIdScheme> NoPadding for CfsCommandHeader<M> {}
+
+impl<M: MsgIdScheme> SecondaryHeader for CfsCommandHeader<M> {
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_command_header::<M>(bytes, bytes.len())
+    }
+}
+
+impl<M: MsgIdScheme> CfsCommandHeader<M> {
+    /// Returns the command code.
+    pub fn function_code(&self) -> u16 {
+        self.bytes[0] as u16
+    }
+
+    /// Returns the contents of the checksum field.
+    pub fn checksum(&self) -> u8 {
+        self.bytes[1]
+    }
 }
 
-/// A cFS-flavor CCSDS telemetry packet, as a Rust structure.
+/// The cFS telemetry secondary header: the timestamp in the time format `F`, plus any structure padding,
+/// for a telemetry message whose message ID is mapped onto the headers by `M`.
 #[repr(C)]
-#[derive(Clone)]
-pub struct Telemetry<T: Copy> {
-    /// The telemetry header.
-    header: [u8; 16],
-
-    /// The message's payload. As messages are copied
-    /// willy-nilly, `T` needs to be [`Copy`].
-    pub payload: T,
+#[derive(Clone, Copy, Debug)]
+pub struct CfsTelemetryHeader<M: MsgIdScheme, F: TimeFormat> {
+    time: F::Header,
+    msg_ids: PhantomData<M>,
+    time_format: PhantomData<F>,
 }
 
-impl<T: Copy> Command<T> {
-    const ALLOWED_MSG_ID_RANGE: core::ops::RangeInclusive<u32> = 0x1800..=0x1FFF;
-    const MAX_FUNCTION_CODE: u16 = 0x7F;
+// Safety: CfsTelemetryHeader is repr(C), and its only non-zero-sized field is AnyBitPattern and NoPadding.
+unsafe impl<M: MsgIdScheme, F: TimeFormat> AnyBitPattern for CfsTelemetryHeader<M, F> {}
+unsafe impl<M: MsgIdScheme, F: TimeFormat> NoPadding for CfsTelemetryHeader<M, F> {}
 
-    /// If `msg_id` and `function_code` are a permissible message ID and a permissible command code (respectively),
-    /// returns a new `Command` with the payload initialized to `payload`; otherwise returns an error.
-    pub fn new(msg_id: u32, function_code: u16, payload: T) -> Result<Self, ()> {
-        let len_field = size_of::<Self>().checked_sub(7).unwrap();
+impl<M: MsgIdScheme, F: TimeFormat> SecondaryHeader for CfsTelemetryHeader<M, F> {
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_telemetry_header::<M>(bytes, bytes.len())
+    }
+}
+
+impl<M: MsgIdScheme, F: TimeFormat> CfsTelemetryHeader<M, F> {
+    /// Returns the timestamp, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        F::timestamp(self.time.as_ref())
+    }
+}
+
+/// A cFS-flavor CCSDS command packet, as a Rust structure,
+/// whose message ID is mapped onto the headers by `M`.
+pub type CommandPacket<T, M> = SpacePacket<CfsCommandHeader<M>, T>;
+
+/// A cFS-flavor CCSDS telemetry packet, as a Rust structure,
+/// whose message ID is mapped onto the headers by `M` and whose timestamp is in the time format `F`.
+pub type TelemetryPacket<T, M, F> = SpacePacket<CfsTelemetryHeader<M, F>, T>;
 
+/// A cFS-flavor CCSDS command packet, as a Rust structure, using cFE's default message ID mapping.
+pub type Command<T> = CommandPacket<T, MsgIdV1>;
+
+/// A cFS-flavor CCSDS telemetry packet, as a Rust structure,
+/// using cFE's default message ID mapping and packet time format.
+pub type Telemetry<T> = TelemetryPacket<T, MsgIdV1, Cfe32_16>;
+
+impl<T: Copy, M: MsgIdScheme> CommandPacket<T, M> {
+    /// If `function_code` is a permissible command code,
+    /// returns a new `Command` with the payload initialized to `payload`; otherwise returns an error.
+    pub fn new(msg_id: CommandMsgId<M>, function_code: u16, payload: T) -> Result<Self, Error> {
         // check that fields are in their allowed ranges
-        if Self::ALLOWED_MSG_ID_RANGE.contains(&msg_id)
-            && function_code <= Self::MAX_FUNCTION_CODE
-            && len_field <= 0xFFFF
-        {
-            #[rustfmt::skip]
-            let header = [
-                //                                             CCSDS primary header:
-                (msg_id >> 8) as u8, msg_id as u8,          // packet version number and packet identification
-                0xC0, 0x00,                                 // packet sequence control
-                (len_field >> 8) as u8, len_field as u8,    // packet data length
-                //                                             cFS secondary header for commands:
-                function_code as u8, 0x00,                  // command code and optional checksum
-            ];
-            Ok(Self { header, payload })
-        } else {
-            Err(())
+        if function_code > MAX_FUNCTION_CODE {
+            return Err(Error::InvalidFunctionCode(function_code));
         }
+
+        let primary = cfs_primary_header::<M>(msg_id.get(), Self::LEN)?;
+
+        // cFS secondary header for commands: command code and optional checksum
+        let secondary = CfsCommandHeader {
+            bytes: [function_code as u8, 0x00],
+            msg_ids: PhantomData,
+        };
+
+        Ok(Self::new_unchecked(primary, secondary, payload))
     }
 
     /// [`Self::new`], but using [`Default::default`]`()` as the payload.
-    pub fn new_default(msg_id: u32, function_code: u16) -> Result<Self, ()>
+    pub fn new_default(msg_id: CommandMsgId<M>, function_code: u16) -> Result<Self, Error>
     where
         T: Default,
     {
         Self::new(msg_id, function_code, Default::default())
     }
 
-    /// Returns a view of the `Command` as a sequence of bytes, ready for transmission.
-    pub fn as_bytes(&self) -> &[u8] {
-        // Safety: all fields of Command<T> are Copy (so no *Cell fields),
-        // and we're using the lifetime of an immutable ref to self.
-        unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    /// [`Self::new`], but with the checksum field set to a valid checksum
+    /// (see [`Self::set_checksum`]) rather than left as zero.
+    pub fn new_with_checksum(
+        msg_id: CommandMsgId<M>,
+        function_code: u16,
+        payload: T,
+    ) -> Result<Self, Error>
+    where
+        T: NoPadding,
+    {
+        let mut cmd = Self::new(msg_id, function_code, payload)?;
+        cmd.set_checksum();
+        Ok(cmd)
     }
 
-    /// Turns a sequence of bytes representing a message into a `Command`,
-    /// assuming `bytes` is the correct length and the header bytes have sane values.
+    /// Updates the checksum field (see [`Self::set_checksum`]),
+    /// then returns a view of the `Command` as a sequence of bytes, ready for transmission.
     ///
-    /// # Safety
-    ///
-    /// Using this function is only safe if the part of `bytes`
-    /// at bytes `8..(8 + std::mem::size_of::<T>())`
-    /// is byte-for-byte equal to a valid item of type `T`.
-    pub unsafe fn from_bytes(bytes: &[u8]) -> Result<Self, ()> {
-        // first off, do sanity checking of message length
-        // and the fields we know how to sanity-check:
-        if bytes.len() != size_of::<Self>() {
-            return Err(());
-        }
-
-        let msg_id = ((bytes[0] as u32) << 8) | (bytes[1] as u32);
-        let msg_len = (((bytes[4] as usize) << 8) | (bytes[5] as usize))
-            .checked_add(7)
-            .unwrap();
-
-        if !(Self::ALLOWED_MSG_ID_RANGE.contains(&msg_id))
-            || (msg_len != size_of::<Self>())
-            || (bytes[2] & 0xC0 != 0xC0)
-            || (bytes[6] & 0x80 != 0x00)
-        {
-            return Err(());
-        }
-
-        // here comes the unsafe part:
-        let mut cmd = core::mem::MaybeUninit::<Self>::uninit();
-        cmd.as_mut_ptr()
-            .write(core::ptr::read_unaligned(bytes.as_ptr() as *const Self));
-        Ok(cmd.assume_init())
+    /// Use this instead of [`Self::as_bytes`] when the receiving app validates command checksums,
+    /// so that changes to the payload or header since the last call to `set_checksum` are accounted for.
+    pub fn as_bytes_with_checksum(&mut self) -> &[u8]
+    where
+        T: NoPadding,
+    {
+        self.set_checksum();
+        self.as_bytes()
     }
 
     /// Returns the message's message ID.
-    pub fn msg_id(&self) -> u32 {
-        ((self.header[0] as u32) >> 8) | (self.header[1] as u32)
+    pub fn msg_id(&self) -> CommandMsgId<M> {
+        CommandMsgId::new_unchecked(M::msg_id(self.primary_header().as_bytes()))
     }
 
     /// Returns the message's command code.
     pub fn function_code(&self) -> u16 {
-        self.header[6] as u16
+        self.secondary_header().function_code()
     }
 
-    /// If `msg_id` is a valid message ID, sets the message's message ID to `msg_id`.
-    pub fn set_msg_id(&mut self, msg_id: u32) -> Result<(), ()> {
-        if Self::ALLOWED_MSG_ID_RANGE.contains(&msg_id) {
-            self.header[0] = (msg_id >> 8) as u8;
-            self.header[1] = msg_id as u8;
-            Ok(())
-        } else {
-            Err(())
-        }
+    /// Returns the contents of the message's checksum field.
+    pub fn checksum(&self) -> u8 {
+        self.secondary_header().checksum()
+    }
+
+    /// Computes the checksum the message's checksum field should contain,
+    /// given the rest of the message's contents.
+    pub fn compute_checksum(&self) -> u8
+    where
+        T: NoPadding,
+    {
+        expected_checksum(self.as_bytes(), self.checksum())
+    }
+
+    /// Sets the message's checksum field to the checksum of the rest of the message,
+    /// as cFE's `CFE_MSG_GenerateChecksum` does.
+    ///
+    /// The checksum is not kept up to date automatically:
+    /// call this again after modifying the header or payload.
+    pub fn set_checksum(&mut self)
+    where
+        T: NoPadding,
+    {
+        self.secondary_header_mut().bytes[1] = self.compute_checksum();
+    }
+
+    /// Returns whether the message's checksum field is consistent with its contents,
+    /// as cFE's `CFE_MSG_ValidateChecksum` would determine.
+    pub fn is_checksum_valid(&self) -> bool
+    where
+        T: NoPadding,
+    {
+        cfe_checksum(self.as_bytes()) == 0
+    }
+
+    /// Returns an error describing the discrepancy if the message's checksum field is inconsistent with its contents.
+    pub fn validate_checksum(&self) -> Result<(), Error>
+    where
+        T: NoPadding,
+    {
+        check_checksum(self.as_bytes(), self.checksum())
+    }
+
+    /// Sets the message's message ID to `msg_id`.
+    pub fn set_msg_id(&mut self, msg_id: CommandMsgId<M>) {
+        M::set_msg_id(self.primary_header_mut().as_bytes_mut(), msg_id.get());
     }
 
     /// If `function_code` is a valid command code, sets the message's function code to `function_code`.
-    pub fn set_function_code(&mut self, function_code: u16) -> Result<(), ()> {
-        if function_code <= Self::MAX_FUNCTION_CODE {
-            self.header[6] = function_code as u8;
+    pub fn set_function_code(&mut self, function_code: u16) -> Result<(), Error> {
+        if function_code <= MAX_FUNCTION_CODE {
+            self.secondary_header_mut().bytes[0] = function_code as u8;
             Ok(())
         } else {
-            Err(())
+            Err(Error::InvalidFunctionCode(function_code))
         }
     }
 }
 
-impl<T: Copy> Telemetry<T> {
-    const ALLOWED_MSG_ID_RANGE: core::ops::RangeInclusive<u32> = 0x0800..=0x0FFF;
+impl<T: Copy, M: MsgIdScheme, F: TimeFormat> TelemetryPacket<T, M, F> {
+    /// Returns a new `Telemetry` with the payload initialized to `payload`,
+    /// or an error if the payload is too large to fit in a packet.
+    pub fn new(msg_id: TelemetryMsgId<M>, payload: T) -> Result<Self, Error> {
+        let primary = cfs_primary_header::<M>(msg_id.get(), Self::LEN)?;
 
-    /// If `msg_id` is a permissible message ID,
-    /// returns a new `Telemetry` with the payload initialized to `payload`; otherwise returns an error.
-    pub fn new(msg_id: u32, payload: T) -> Result<Self, ()> {
-        let len_field = size_of::<Self>().checked_sub(7).unwrap();
+        // cFS secondary header for telemetry (timestamp and any structure padding) starts zeroed
+        let secondary = CfsTelemetryHeader {
+            time: F::Header::default(),
+            msg_ids: PhantomData,
+            time_format: PhantomData,
+        };
 
-        // check that fields are in their allowed ranges
-        if Self::ALLOWED_MSG_ID_RANGE.contains(&msg_id) && len_field <= 0xFFFF {
-            #[rustfmt::skip]
-            let header = [
-                //                                             CCSDS primary header:
-                (msg_id >> 8) as u8, msg_id as u8,          // packet version number and packet identification
-                0xC0, 0x00,                                 // packet sequence control
-                (len_field >> 8) as u8, len_field as u8,    // packet data length
-                //                                             cFS secondary header for telemetry, default contents:
-                0, 0, 0, 0,                                 // timestamp (seconds part)
-                0, 0,                                       // timestamp (subseconds part)
-                0, 0, 0, 0,                                 // structure padding
-            ];
-            Ok(Self { header, payload })
-        } else {
-            Err(())
-        }
+        Ok(Self::new_unchecked(primary, secondary, payload))
     }
 
     /// [`Self::new`], but using [`Default::default`]`()` as the payload.
-    pub fn new_default(msg_id: u32) -> Result<Self, ()>
+    pub fn new_default(msg_id: TelemetryMsgId<M>) -> Result<Self, Error>
     where
         T: Default,
     {
         Self::new(msg_id, Default::default())
     }
 
-    /// Returns a view of the `Telemetry` as a sequence of bytes, ready for transmission.
-    pub fn as_bytes(&self) -> &[u8] {
-        // Safety: all fields of Telemetry<T> are Copy (so no *Cell fields),
-        // and we're using the lifetime of an immutable ref to self.
-        unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    /// Returns the message's message ID.
+    pub fn msg_id(&self) -> TelemetryMsgId<M> {
+        TelemetryMsgId::new_unchecked(M::msg_id(self.primary_header().as_bytes()))
     }
 
-    /// Turns a sequence of bytes representing a message into a `Telemetry`,
-    /// assuming `bytes` is the correct length and the header bytes have sane values.
-    ///
-    /// # Safety
-    ///
-    /// Using this function is only safe if the part of `bytes`
-    /// at bytes `16..(16 + std::mem::size_of::<T>())`
-    /// is byte-for-byte equal to a valid item of type `T`.
-    pub unsafe fn from_bytes(bytes: &[u8]) -> Result<Self, ()> {
-        // first off, do sanity checking of message length
-        // and the fields we know how to sanity-check:
-        if bytes.len() != size_of::<Self>() {
-            return Err(());
-        }
+    /// Returns the message's timestamp, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        self.secondary_header().timestamp()
+    }
 
-        let msg_id = ((bytes[0] as u32) << 8) | (bytes[1] as u32);
-        let msg_len = (((bytes[4] as usize) << 8) | (bytes[5] as usize))
-            .checked_add(7)
-            .unwrap();
+    /// Returns the message's timestamp as a UTC time,
+    /// taking the spacecraft clock to be configured as in `config`.
+    pub fn timestamp_utc(&self, config: &TimeConfig) -> UtcTime {
+        config.to_utc(F::to_duration(self.timestamp()))
+    }
 
-        if !(Self::ALLOWED_MSG_ID_RANGE.contains(&msg_id))
-            || (msg_len != size_of::<Self>())
-            || (bytes[2] & 0xC0 != 0xC0)
-        {
-            return Err(());
-        }
+    /// Sets the message's message ID to `msg_id`.
+    pub fn set_msg_id(&mut self, msg_id: TelemetryMsgId<M>) {
+        M::set_msg_id(self.primary_header_mut().as_bytes_mut(), msg_id.get());
+    }
 
-        // here comes the unsafe part:
-        let mut tlm = core::mem::MaybeUninit::<Self>::uninit();
-        tlm.as_mut_ptr()
-            .write(core::ptr::read_unaligned(bytes.as_ptr() as *const Self));
-        Ok(tlm.assume_init())
+    /// Sets the message's timestamp to
+    /// `seconds` seconds + `nanoseconds` nanoseconds
+    /// since the flight-software epoch, rounded down to the resolution of the time format `F`.
+    pub fn set_timestamp(&mut self, seconds: u64, nanoseconds: u32) {
+        self.set_timestamp_to(F::from_secs_nanos(seconds, nanoseconds));
     }
 
-    /// Returns the message's message ID.
-    pub fn msg_id(&self) -> u32 {
-        ((self.header[0] as u32) >> 8) | (self.header[1] as u32)
+    /// Sets the message's timestamp to `timestamp`, given in the form used by the time format `F`.
+    pub fn set_timestamp_to(&mut self, timestamp: F::Timestamp) {
+        F::set_timestamp(self.secondary_header_mut().time.as_mut(), timestamp);
     }
 
-    /// Returns the message's timestamp as a tuple of
-    /// (seconds since flight-software epoch, subseconds in units of 2<sup>&minus;16</sup> s).
-    pub fn timestamp(&self) -> (u32, u16) {
-        let seconds = ((self.header[6] as u32) << 24)
-            | ((self.header[7] as u32) << 16)
-            | ((self.header[8] as u32) << 8)
-            | (self.header[9] as u32);
-        let subsecs = ((self.header[10] as u16) << 8) | (self.header[11] as u16);
+    /// Sets the message's timestamp to the current time according to `source`.
+    pub fn timestamp_with<S: TimeSource + ?Sized>(&mut self, source: &S) -> Result<(), Error> {
+        let since_epoch = source.now()?;
 
-        (seconds, subsecs)
+        self.set_timestamp(since_epoch.as_secs(), since_epoch.subsec_nanos());
+        Ok(())
     }
 
-    /// Returns the message's sequence number.
-    pub fn sequence_number(&self) -> u16 {
-        let sequence_header = ((self.header[2] as u16) << 8) | (self.header[3] as u16);
-
-        sequence_header & 0x3FFF
+    /// Sets the message's timestamp to the current time, counted in UTC seconds
+    /// (without leap seconds) since 1980-01-01T00:00:00 UTC.
+    ///
+    /// Fails with [`Error::TimeOutOfRange`] if the host's clock reads earlier than the epoch.
+    /// Use [`Self::timestamp_with_now_in`] for a spacecraft clock with another epoch or time scale.
+    #[cfg(feature = "std")]
+    pub fn timestamp_with_now(&mut self) -> Result<(), Error> {
+        self.set_timestamp_to(system_time_now::<F>()?);
+        Ok(())
     }
 
-    /// If `msg_id` is a valid message ID, uses it to set the message's message ID.
-    pub fn set_msg_id(&mut self, msg_id: u32) -> Result<(), ()> {
-        if Self::ALLOWED_MSG_ID_RANGE.contains(&msg_id) {
-            self.header[0] = (msg_id >> 8) as u8;
-            self.header[1] = msg_id as u8;
-            Ok(())
-        } else {
-            Err(())
-        }
+    /// Sets the message's timestamp to the current time,
+    /// for a spacecraft clock configured as in `config`.
+    #[cfg(feature = "std")]
+    pub fn timestamp_with_now_in(&mut self, config: &TimeConfig) -> Result<(), Error> {
+        self.timestamp_with(&SystemTimeSource::new(*config))
     }
+}
 
-    /// Sets the message's timestamp to
-    /// `seconds` seconds + `nanoseconds` nanoseconds
-    /// since the flight-software epoch, rounded to 2<sup>&minus;16</sup> seconds.
-    pub fn set_timestamp(&mut self, seconds: u64, nanoseconds: u32) {
-        // the 4-byte seconds field is seconds since epoch,
-        // the 2-byte subseconds field is fractional part of time (in units of 2^-16 second)
+/// Returns the current time according to the host's clock, in the time format `F`,
+/// counted in UTC seconds (without leap seconds) since 1980-01-01T00:00:00 UTC.
+#[cfg(feature = "std")]
+fn system_time_now<F: TimeFormat>() -> Result<F::Timestamp, Error> {
+    use std::time::SystemTime;
 
-        // subseconds, in units of 2^-16 sec
-        let subsecs = (nanoseconds as u64 * (1 << 16)) / 1_000_000_000;
+    let epoch_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH + FLIGHT_SOFTWARE_EPOCH)
+        .map_err(|_| Error::TimeOutOfRange)?;
 
-        self.header[6] = (seconds >> 24) as u8;
-        self.header[7] = (seconds >> 16) as u8;
-        self.header[8] = (seconds >> 8) as u8;
-        self.header[9] = seconds as u8;
-        self.header[10] = (subsecs >> 8) as u8;
-        self.header[11] = subsecs as u8;
-    }
+    Ok(F::from_secs_nanos(
+        epoch_time.as_secs(),
+        epoch_time.subsec_nanos(),
+    ))
+}
 
-    /// Sets the message's timestamp to the current time.
-    #[cfg(feature = "std")]
-    pub fn timestamp_with_now(&mut self) -> Result<(), std::time::SystemTimeError> {
-        use std::time::SystemTime;
+/// Builds the primary header of an unsegmented cFS packet with the message ID `msg_id`
+/// (which should already have been checked) that's `packet_len` bytes long in total.
+fn cfs_primary_header<M: MsgIdScheme>(
+    msg_id: u32,
+    packet_len: usize,
+) -> Result<PrimaryHeader, Error> {
+    let () = msg_id::NoExtendedHeader::<M>::CHECK;
+
+    let mut primary = PrimaryHeader::default();
+    M::set_msg_id(primary.as_bytes_mut(), msg_id);
+    primary.set_sequence_flags(SequenceFlags::Unsegmented);
+    primary.set_packet_len(packet_len)?;
+    Ok(primary)
+}
 
-        let epoch_time =
-            SystemTime::now().duration_since(SystemTime::UNIX_EPOCH + FLIGHT_SOFTWARE_EPOCH)?;
+/// Computes the cFE command checksum of `bytes`: `0xFF`, XORed with every byte of `bytes`.
+///
+/// For a command whose checksum field holds a valid checksum,
+/// the checksum of the whole packet is zero.
+pub fn cfe_checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0xFF, |acc, byte| acc ^ byte)
+}
+
+/// Computes the checksum the checksum field of the command `bytes` should contain,
+/// given that it currently contains `checksum`.
+fn expected_checksum(bytes: &[u8], checksum: u8) -> u8 {
+    // the checksum is computed with the checksum field zeroed out,
+    // so cancel out the current contents of the field:
+    cfe_checksum(bytes) ^ checksum
+}
 
-        self.set_timestamp(epoch_time.as_secs(), epoch_time.subsec_nanos());
+/// Returns an error describing the discrepancy if the checksum field of the command `bytes`,
+/// which contains `checksum`, is inconsistent with the rest of the command.
+fn check_checksum(bytes: &[u8], checksum: u8) -> Result<(), Error> {
+    if cfe_checksum(bytes) == 0 {
         Ok(())
+    } else {
+        Err(Error::ChecksumMismatch {
+            expected: expected_checksum(bytes, checksum),
+            actual: checksum,
+        })
     }
+}
+
+/// Does the sanity checks on a command's headers: those of [`check_primary_header`],
+/// plus checking that the reserved bit in the secondary header is clear.
+fn check_command_header<M: MsgIdScheme>(bytes: &[u8], expected_len: usize) -> Result<(), Error> {
+    let () = msg_id::NoExtendedHeader::<M>::CHECK;
+
+    check_primary_header::<M>(bytes, expected_len, PacketType::Command)?;
+
+    if bytes[6] & 0x80 != 0x00 {
+        return Err(Error::ReservedBitSet(bytes[6]));
+    }
+
+    Ok(())
+}
 
-    /// Increment the message's sequence number.
-    pub fn increment_sequence_num(&mut self) {
-        let sequence_header = ((self.header[2] as u16) << 8) | (self.header[3] as u16);
+/// Does the sanity checks on a telemetry message's headers.
+fn check_telemetry_header<M: MsgIdScheme>(bytes: &[u8], expected_len: usize) -> Result<(), Error> {
+    let () = msg_id::NoExtendedHeader::<M>::CHECK;
 
-        let new_sequence_header = (sequence_header.wrapping_add(1) & 0x3FFF) | 0xC000;
+    check_primary_header::<M>(bytes, expected_len, PacketType::Telemetry)
+}
+
+/// Does the sanity checks on a packet's primary header that are common to commands and telemetry:
+/// checks that `bytes` is `expected_len` bytes long and agrees with the packet data length field,
+/// that the packet version number is 0 and the packet type is `packet_type`,
+/// that the message ID is one `M` allows for `packet_type`, and that the packet is unsegmented.
+fn check_primary_header<M: MsgIdScheme>(
+    bytes: &[u8],
+    expected_len: usize,
+    packet_type: PacketType,
+) -> Result<(), Error> {
+    if bytes.len() != expected_len {
+        return Err(Error::WrongLength {
+            expected: expected_len,
+            actual: bytes.len(),
+        });
+    }
+
+    let header = PrimaryHeader::from_bytes(bytes[..PrimaryHeader::LEN].try_into().unwrap());
+    let msg_id = M::msg_id(bytes);
 
-        self.header[2] = (new_sequence_header >> 8) as u8;
-        self.header[3] = new_sequence_header as u8;
+    if header.version() != 0 || header.packet_type() != packet_type {
+        return Err(Error::InvalidVersionOrType(bytes[0]));
     }
+    if !M::msg_ids(packet_type).contains(msg_id) {
+        return Err(Error::InvalidMsgId(msg_id));
+    }
+    if header.packet_len() != expected_len {
+        return Err(Error::LengthFieldMismatch {
+            expected: expected_len,
+            actual: header.packet_len(),
+        });
+    }
+    if header.sequence_flags() != SequenceFlags::Unsegmented {
+        return Err(Error::InvalidSequenceFlags(bytes[2]));
+    }
+
+    Ok(())
 }
 
 /// Takes a `str` or `String` and uses it to populate an array of `c_char`s.
@@ -316,7 +498,7 @@ pub fn fill_char_array<S: AsRef<[u8]>, const N: usize>(
 
     let max_untruncated_len = if ensure_null_termination { N - 1 } else { N };
     let is_truncated = (bytes.len() > max_untruncated_len)
-        || (bytes.len() == max_untruncated_len && !(bytes.iter().any(|b| b'\0' == *b)));
+        || (bytes.len() == max_untruncated_len && !bytes.contains(&b'\0'));
 
     for (i, in_byte) in bytes.iter().take(max_untruncated_len).enumerate() {
         output[i] = (*in_byte) as c_char;
@@ -324,3 +506,47 @@ pub fn fill_char_array<S: AsRef<[u8]>, const N: usize>(
 
     (output, is_truncated)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cfe_checksum_known_answer() {
+        assert_eq!(cfe_checksum(&[]), 0xFF);
+        assert_eq!(cfe_checksum(&[0xFF]), 0x00);
+        assert_eq!(cfe_checksum(&[0x12, 0x34, 0x56]), 0x8F);
+
+        let mut cmd = Command::new(CommandMsgId::new(0x1880), 3, [0x01u8, 0x02]).unwrap();
+        assert_eq!(cmd.compute_checksum(), 0xA4);
+        assert_eq!(
+            cmd.as_bytes_with_checksum(),
+            [0x18, 0x80, 0xC0, 0x00, 0x00, 0x03, 0x03, 0xA4, 0x01, 0x02]
+        );
+        assert!(cmd.is_checksum_valid());
+
+        cmd.payload[1] = 0x03;
+        assert!(!cmd.is_checksum_valid());
+        assert_eq!(
+            cmd.validate_checksum(),
+            Err(Error::ChecksumMismatch {
+                expected: 0xA5,
+                actual: 0xA4
+            })
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timestamp_with_now_reads_the_host_clock() -> Result<(), Error> {
+        let mut tlm = Telemetry::new(TelemetryMsgId::new(0x0880), [0u8; 2])?;
+        tlm.timestamp_with_now()?;
+        // 2018-01-14, give or take, in seconds since 1980
+        assert!(tlm.timestamp().0 > 1_200_000_000);
+
+        let mut tlm = ExtTelemetry::new(TelemetryMsgId::new(0x1A05), [0u8; 2])?;
+        tlm.timestamp_with_now()?;
+        assert!(tlm.timestamp().0 > 1_200_000_000);
+        Ok(())
+    }
+}
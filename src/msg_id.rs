@@ -0,0 +1,381 @@
+//! Mappings between cFS message IDs and the packet header fields that encode them.
+//!
+//! Packet types are generic over a [`MsgIdScheme`]; [`Command`](crate::Command), [`Telemetry`](crate::Telemetry)
+//! and friends use cFE's default mapping, [`MsgIdV1`]. A mission with its own message ID ranges
+//! implements [`MsgIdScheme`] on a type of its own and uses it through aliases like
+//! `type MissionCommand<T> = CommandPacket<T, MissionMsgIds>;`.
+//!
+//! Message IDs themselves are passed around as [`CommandMsgId`]s and [`TelemetryMsgId`]s,
+//! which are checked against the scheme's ranges when they're made,
+//! so that (for instance) a telemetry message ID can't be used to build a command.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::raw::{primary_header, primary_header_mut};
+use crate::{Error, ExtendedHeader, PacketType, PrimaryHeader};
+
+/// The longest run of headers a [`MsgIdScheme`] reads message IDs from:
+/// a primary header and an extended header.
+const MAX_HEADERS_LEN: usize = PrimaryHeader::LEN + ExtendedHeader::LEN;
+
+/// A set of message IDs: those between `min` and `max` (inclusive)
+/// whose bits selected by `mask` are equal to those of `value`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MsgIdRange {
+    min: u32,
+    max: u32,
+    mask: u32,
+    value: u32,
+}
+
+impl MsgIdRange {
+    /// Returns the set of message IDs from `min` to `max`, inclusive.
+    pub const fn new(min: u32, max: u32) -> Self {
+        Self {
+            min,
+            max,
+            mask: 0,
+            value: 0,
+        }
+    }
+
+    /// Returns the message IDs in `self` whose bits selected by `mask` are equal to those of `value`.
+    pub const fn with_mask(self, mask: u32, value: u32) -> Self {
+        Self {
+            mask,
+            value: value & mask,
+            ..self
+        }
+    }
+
+    /// Returns the smallest message ID in the range (assuming it matches the mask).
+    pub const fn min(&self) -> u32 {
+        self.min
+    }
+
+    /// Returns the largest message ID in the range (assuming it matches the mask).
+    pub const fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Returns whether `msg_id` is in the set.
+    pub const fn contains(&self, msg_id: u32) -> bool {
+        self.min <= msg_id && msg_id <= self.max && msg_id & self.mask == self.value
+    }
+}
+
+/// A mapping between cFS message IDs and packet headers,
+/// along with the message IDs that commands and telemetry messages may have.
+///
+/// The provided methods implement cFE's default mapping, in which the message ID is the first 16 bits
+/// of the primary header (see [`MsgIdV1`]); a mission that only uses different message ID ranges
+/// just needs to set [`Self::COMMAND_MSG_IDS`] and [`Self::TELEMETRY_MSG_IDS`].
+pub trait MsgIdScheme: Copy + core::fmt::Debug + 'static {
+    /// The message IDs commands may have.
+    const COMMAND_MSG_IDS: MsgIdRange;
+
+    /// The message IDs telemetry messages may have.
+    const TELEMETRY_MSG_IDS: MsgIdRange;
+
+    /// Whether the mapping uses fields of the cFE extended header, which follows the primary header.
+    /// Packets without an extended header can't use such a scheme.
+    const EXTENDED_HEADER: bool = false;
+
+    /// Returns the message IDs that packets of type `packet_type` may have.
+    fn msg_ids(packet_type: PacketType) -> MsgIdRange {
+        match packet_type {
+            PacketType::Command => Self::COMMAND_MSG_IDS,
+            PacketType::Telemetry => Self::TELEMETRY_MSG_IDS,
+        }
+    }
+
+    /// Returns the message ID encoded in `headers`: a packet's primary header,
+    /// followed by its extended header if [`Self::EXTENDED_HEADER`] is set.
+    fn msg_id(headers: &[u8]) -> u32 {
+        primary_header(headers).stream_id() as u32
+    }
+
+    /// Encodes `msg_id` (which should already have been checked) into `headers`:
+    /// a packet's primary header, followed by its extended header if [`Self::EXTENDED_HEADER`] is set.
+    fn set_msg_id(headers: &mut [u8], msg_id: u32) {
+        primary_header_mut(headers).set_stream_id(msg_id as u16);
+    }
+}
+
+/// cFE's default message ID mapping: the message ID is the first 16 bits of the primary header,
+/// with command message IDs in `0x1800..=0x1FFF` and telemetry message IDs in `0x0800..=0x0FFF`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MsgIdV1;
+
+impl MsgIdScheme for MsgIdV1 {
+    const COMMAND_MSG_IDS: MsgIdRange = MsgIdRange::new(0x1800, 0x1FFF);
+    const TELEMETRY_MSG_IDS: MsgIdRange = MsgIdRange::new(0x0800, 0x0FFF);
+}
+
+/// cFE's message ID mapping for packets with the extended header:
+/// bits 0&ndash;6 of the message ID are the low 7 bits of the APID, bit 7 is set for commands,
+/// and bits 8&ndash;15 are the low 8 bits of the extended header's subsystem ID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MsgIdV2;
+
+impl MsgIdScheme for MsgIdV2 {
+    const COMMAND_MSG_IDS: MsgIdRange = MsgIdRange::new(0x0000, 0xFFFF).with_mask(0x80, 0x80);
+    const TELEMETRY_MSG_IDS: MsgIdRange = MsgIdRange::new(0x0000, 0xFFFF).with_mask(0x80, 0x00);
+    const EXTENDED_HEADER: bool = true;
+
+    fn msg_id(headers: &[u8]) -> u32 {
+        let primary = primary_header(headers);
+        let command_flag = match primary.packet_type() {
+            PacketType::Command => 0x80,
+            PacketType::Telemetry => 0x00,
+        };
+
+        ((extended_header(headers).subsystem() as u32 & 0xFF) << 8)
+            | command_flag
+            | (primary.apid() & 0x7F) as u32
+    }
+
+    fn set_msg_id(headers: &mut [u8], msg_id: u32) {
+        let primary = primary_header_mut(headers);
+        primary.set_packet_type(if msg_id & 0x80 != 0 {
+            PacketType::Command
+        } else {
+            PacketType::Telemetry
+        });
+        primary.set_apid((msg_id & 0x7F) as u16).unwrap();
+
+        extended_header_mut(headers)
+            .set_subsystem(((msg_id >> 8) & 0xFF) as u16)
+            .unwrap();
+    }
+}
+
+macro_rules! msg_id_type {
+    ($name:ident, $packet_type:ident, $range:ident, $kind:literal) => {
+        #[doc = concat!("A ", $kind, " message ID, checked to be one that the message ID scheme `M` allows for ", $kind, " packets.")]
+        pub struct $name<M: MsgIdScheme = MsgIdV1> {
+            msg_id: u32,
+            msg_ids: PhantomData<M>,
+        }
+
+        impl<M: MsgIdScheme> $name<M> {
+            /// Wraps `msg_id`, panicking if `M` doesn't allow it.
+            ///
+            /// When used to define a constant, this fails at compile time instead.
+            pub const fn new(msg_id: u32) -> Self {
+                match Self::try_new(msg_id) {
+                    Ok(msg_id) => msg_id,
+                    Err(_) => panic!(concat!("message ID out of range for ", $kind, " packets")),
+                }
+            }
+
+            /// Wraps `msg_id`, if `M` allows it.
+            pub const fn try_new(msg_id: u32) -> Result<Self, Error> {
+                if M::$range.contains(msg_id) {
+                    Ok(Self::new_unchecked(msg_id))
+                } else {
+                    Err(Error::InvalidMsgId(msg_id))
+                }
+            }
+
+            /// Wraps `msg_id` without checking it, for message IDs read out of already-checked headers.
+            pub(crate) const fn new_unchecked(msg_id: u32) -> Self {
+                Self {
+                    msg_id,
+                    msg_ids: PhantomData,
+                }
+            }
+
+            #[doc = concat!("Returns the message ID `M` gives ", $kind, " packets with the APID `apid`")]
+            /// (and, for schemes using the extended header, an otherwise-zeroed extended header),
+            /// or an error if there's no such message ID.
+            pub fn from_apid(apid: u16) -> Result<Self, Error> {
+                let mut headers = [0; MAX_HEADERS_LEN];
+                let primary = primary_header_mut(&mut headers);
+                primary.set_packet_type(PacketType::$packet_type);
+                primary.set_secondary_header_flag(true);
+                primary.set_apid(apid)?;
+
+                let msg_id = Self::try_new(M::msg_id(&headers))?;
+                // the scheme may not have room for every bit of the APID
+                if msg_id.apid() != apid {
+                    return Err(Error::InvalidApid(apid));
+                }
+                Ok(msg_id)
+            }
+
+            /// Returns the APID of packets with this message ID.
+            pub fn apid(self) -> u16 {
+                let mut headers = [0; MAX_HEADERS_LEN];
+                M::set_msg_id(&mut headers, self.msg_id);
+                primary_header(&headers).apid()
+            }
+
+            /// Returns the message ID as a number.
+            pub const fn get(self) -> u32 {
+                self.msg_id
+            }
+        }
+
+        impl<M: MsgIdScheme> Clone for $name<M> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<M: MsgIdScheme> Copy for $name<M> {}
+
+        impl<M: MsgIdScheme> PartialEq for $name<M> {
+            fn eq(&self, other: &Self) -> bool {
+                self.msg_id == other.msg_id
+            }
+        }
+
+        impl<M: MsgIdScheme> Eq for $name<M> {}
+
+        impl<M: MsgIdScheme> PartialOrd for $name<M> {
+            fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl<M: MsgIdScheme> Ord for $name<M> {
+            fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+                self.msg_id.cmp(&other.msg_id)
+            }
+        }
+
+        impl<M: MsgIdScheme> core::hash::Hash for $name<M> {
+            fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                self.msg_id.hash(state);
+            }
+        }
+
+        impl<M: MsgIdScheme> fmt::Debug for $name<M> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_tuple(stringify!($name))
+                    .field(&format_args!("{:#06X}", self.msg_id))
+                    .finish()
+            }
+        }
+
+        impl<M: MsgIdScheme> fmt::Display for $name<M> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{:#06X}", self.msg_id)
+            }
+        }
+
+        impl<M: MsgIdScheme> From<$name<M>> for u32 {
+            fn from(msg_id: $name<M>) -> Self {
+                msg_id.msg_id
+            }
+        }
+
+        impl<M: MsgIdScheme> TryFrom<u32> for $name<M> {
+            type Error = Error;
+
+            fn try_from(msg_id: u32) -> Result<Self, Error> {
+                Self::try_new(msg_id)
+            }
+        }
+    };
+}
+
+msg_id_type!(CommandMsgId, Command, COMMAND_MSG_IDS, "command");
+msg_id_type!(TelemetryMsgId, Telemetry, TELEMETRY_MSG_IDS, "telemetry");
+
+/// Checked at compile time by packet types without an extended header.
+pub(crate) struct NoExtendedHeader<M>(PhantomData<M>);
+
+impl<M: MsgIdScheme> NoExtendedHeader<M> {
+    pub(crate) const CHECK: () = assert!(
+        !M::EXTENDED_HEADER,
+        "this message ID scheme needs packets with a cFE extended header"
+    );
+}
+
+/// Returns the extended header following the primary header at the start of `headers`.
+fn extended_header(headers: &[u8]) -> &ExtendedHeader {
+    ExtendedHeader::from_bytes_ref(
+        headers[PrimaryHeader::LEN..MAX_HEADERS_LEN]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// Returns the extended header following the primary header at the start of `headers`.
+fn extended_header_mut(headers: &mut [u8]) -> &mut ExtendedHeader {
+    ExtendedHeader::from_bytes_mut(
+        (&mut headers[PrimaryHeader::LEN..MAX_HEADERS_LEN])
+            .try_into()
+            .unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_maps_msg_ids_to_stream_ids() {
+        let mut headers = [0; MAX_HEADERS_LEN];
+        MsgIdV1::set_msg_id(&mut headers, 0x1880);
+        assert_eq!(headers[..2], [0x18, 0x80]);
+        assert_eq!(MsgIdV1::msg_id(&headers), 0x1880);
+
+        assert_eq!(
+            CommandMsgId::<MsgIdV1>::from_apid(0x80).unwrap().get(),
+            0x1880
+        );
+        assert_eq!(
+            TelemetryMsgId::<MsgIdV1>::from_apid(0x80).unwrap().get(),
+            0x0880
+        );
+        assert_eq!(TelemetryMsgId::<MsgIdV1>::new(0x0880).apid(), 0x80);
+        assert_eq!(
+            CommandMsgId::<MsgIdV1>::try_new(0x0880),
+            Err(Error::InvalidMsgId(0x0880))
+        );
+    }
+
+    #[test]
+    fn v2_maps_msg_ids_to_apid_and_subsystem() {
+        let mut headers = [0; MAX_HEADERS_LEN];
+        MsgIdV2::set_msg_id(&mut headers, 0x1A85);
+        assert_eq!(headers, [0x10, 0x05, 0, 0, 0, 0, 0x00, 0x1A, 0, 0]);
+        assert_eq!(MsgIdV2::msg_id(&headers), 0x1A85);
+
+        MsgIdV2::set_msg_id(&mut headers, 0xFF7F);
+        assert_eq!(headers, [0x00, 0x7F, 0, 0, 0, 0, 0x00, 0xFF, 0, 0]);
+        assert_eq!(MsgIdV2::msg_id(&headers), 0xFF7F);
+
+        // only the low bits of the APID and the subsystem ID make it into the message ID
+        let headers = [0x1F, 0xFF, 0, 0, 0, 0, 0x01, 0xFF, 0, 0];
+        assert_eq!(MsgIdV2::msg_id(&headers), 0xFFFF);
+    }
+
+    #[test]
+    fn v2_checks_msg_id_ranges() {
+        assert!(CommandMsgId::<MsgIdV2>::try_new(0x1A85).is_ok());
+        assert_eq!(
+            CommandMsgId::<MsgIdV2>::try_new(0x1A05),
+            Err(Error::InvalidMsgId(0x1A05))
+        );
+        assert_eq!(
+            TelemetryMsgId::<MsgIdV2>::try_new(0x1A85),
+            Err(Error::InvalidMsgId(0x1A85))
+        );
+
+        assert_eq!(CommandMsgId::<MsgIdV2>::from_apid(5).unwrap().get(), 0x0085);
+        assert_eq!(
+            TelemetryMsgId::<MsgIdV2>::from_apid(0x7F).unwrap().get(),
+            0x007F
+        );
+        assert_eq!(
+            CommandMsgId::<MsgIdV2>::from_apid(0x80),
+            Err(Error::InvalidApid(0x80))
+        );
+        assert_eq!(CommandMsgId::<MsgIdV2>::new(0x1A85).apid(), 5);
+    }
+}
@@ -0,0 +1,313 @@
+//! Space packets as Rust structures, generic over the secondary header,
+//! so that missions can use secondary headers of their own (or none at all).
+//!
+//! The cFS command and telemetry packets are [`SpacePacket`]s with one of the cFS secondary headers:
+//! [`CommandPacket`](crate::CommandPacket) and [`TelemetryPacket`](crate::TelemetryPacket) are aliases.
+
+use core::mem::size_of;
+
+use crate::{
+    AnyBitPattern, ConvertEndian, Endianness, Error, NoPadding, PacketType, PrimaryHeader,
+};
+
+/// A secondary header, as laid out on the wire directly after the primary header.
+///
+/// The header's length is its size, and it's sent and received as its bytes in memory,
+/// so it must be free of padding, and any byte pattern must be a valid header.
+/// `()` stands for no secondary header at all.
+pub trait SecondaryHeader: AnyBitPattern + NoPadding {
+    /// Checks the headers at the start of `bytes`, a received packet
+    /// that's already been checked to be as long as a packet with this secondary header should be.
+    ///
+    /// By default, checks that the packet version number is 0,
+    /// that the secondary header flag is set if (and only if) the secondary header isn't empty,
+    /// and that the packet data length field agrees with the length of `bytes`.
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_space_packet::<Self>(bytes)
+    }
+
+    /// Checks a received packet with this secondary header as a whole,
+    /// for anything the header's standard requires of the rest of the packet.
+    /// Called by [`SpacePacket::from_bytes`] once [`Self::validate`] has passed.
+    ///
+    /// By default, there's nothing to check.
+    fn validate_packet(bytes: &[u8]) -> Result<(), Error> {
+        let _ = bytes;
+        Ok(())
+    }
+}
+
+impl SecondaryHeader for () {}
+
+/// A CCSDS space packet, as a Rust structure:
+/// a primary header, a secondary header of type `H`, then a payload of type `T`.
+///
+/// The headers and payload must be laid out back to back, so `T` must not be so strictly aligned
+/// that padding is needed after the headers; building or parsing such a packet fails to compile.
+#[repr(C)]
+#[derive(Clone)]
+pub struct SpacePacket<H: SecondaryHeader, T: Copy> {
+    /// The CCSDS primary header.
+    primary: PrimaryHeader,
+
+    /// The secondary header.
+    secondary: H,
+
+    /// The message's payload. As messages are copied
+    /// willy-nilly, `T` needs to be [`Copy`].
+    pub payload: T,
+}
+
+impl<H: SecondaryHeader, T: Copy> SpacePacket<H, T> {
+    /// The length of the packet on the wire, in bytes.
+    pub const LEN: usize = PrimaryHeader::LEN + size_of::<H>() + size_of::<T>();
+
+    /// Returns a new packet made up of `primary`, `secondary` and `payload`,
+    /// or an error if the payload is too large to fit in a packet.
+    ///
+    /// The secondary header flag and packet data length field of `primary` are set to suit;
+    /// its other fields are left as they are.
+    pub fn from_parts(mut primary: PrimaryHeader, secondary: H, payload: T) -> Result<Self, Error> {
+        primary.set_secondary_header_flag(size_of::<H>() != 0);
+        primary.set_packet_len(Self::LEN)?;
+
+        Ok(Self::new_unchecked(primary, secondary, payload))
+    }
+
+    /// Puts together a packet from headers that have already been set up to suit it.
+    pub(crate) fn new_unchecked(primary: PrimaryHeader, secondary: H, payload: T) -> Self {
+        let () = Self::NO_PADDING;
+
+        Self {
+            primary,
+            secondary,
+            payload,
+        }
+    }
+
+    /// Checked at compile time whenever a packet is built, parsed or viewed as bytes:
+    /// the headers and payload must be laid out back to back,
+    /// with no padding between them or after the payload.
+    const NO_PADDING: () = assert!(
+        size_of::<Self>() == Self::LEN,
+        "payload alignment forces padding into the packet"
+    );
+
+    /// Returns a view of the packet as a sequence of bytes, ready for transmission.
+    ///
+    /// `T` must be free of padding (so that no uninitialized bytes are read),
+    /// and must not be so strictly aligned that padding is needed after the headers.
+    pub fn as_bytes(&self) -> &[u8]
+    where
+        T: NoPadding,
+    {
+        let () = Self::NO_PADDING;
+
+        // Safety: all fields of SpacePacket<H, T> are Copy (so no *Cell fields),
+        // none of them have padding (nor is there padding between them),
+        // and we're using the lifetime of an immutable ref to self.
+        unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, Self::LEN) }
+    }
+
+    /// Turns a sequence of bytes representing a message into a packet,
+    /// assuming `bytes` is the correct length and the packet passes
+    /// [`SecondaryHeader::validate`] and [`SecondaryHeader::validate_packet`].
+    ///
+    /// As any byte pattern is a valid `T`, this is safe;
+    /// for payload types that aren't [`AnyBitPattern`], see [`Self::from_bytes_unchecked`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error>
+    where
+        T: AnyBitPattern,
+    {
+        // Safety: every byte pattern is a valid T.
+        let packet = unsafe { Self::from_bytes_unchecked(bytes) }?;
+        H::validate_packet(bytes)?;
+        Ok(packet)
+    }
+
+    /// Turns a sequence of bytes representing a message into a packet,
+    /// assuming `bytes` is the correct length and the headers pass [`SecondaryHeader::validate`].
+    ///
+    /// The headers are checked just as in [`Self::from_bytes`]; the payload's validity is not,
+    /// and neither is anything [`SecondaryHeader::validate_packet`] would check.
+    ///
+    /// # Safety
+    ///
+    /// Using this function is only safe if the part of `bytes` following the headers
+    /// (the last `std::mem::size_of::<T>()` bytes)
+    /// is byte-for-byte equal to a valid item of type `T`.
+    pub unsafe fn from_bytes_unchecked(bytes: &[u8]) -> Result<Self, Error> {
+        let () = Self::NO_PADDING;
+
+        // first off, do sanity checking of message length
+        // and the fields the secondary header knows how to sanity-check:
+        if bytes.len() != Self::LEN {
+            return Err(Error::WrongLength {
+                expected: Self::LEN,
+                actual: bytes.len(),
+            });
+        }
+        H::validate(bytes)?;
+
+        // here comes the unsafe part:
+        let mut packet = core::mem::MaybeUninit::<Self>::uninit();
+        packet
+            .as_mut_ptr()
+            .write(core::ptr::read_unaligned(bytes.as_ptr() as *const Self));
+        Ok(packet.assume_init())
+    }
+
+    /// Returns a copy of the message with its payload converted from host byte order
+    /// to the byte order `order` used by the flight software, ready for [`Self::as_bytes`].
+    ///
+    /// Headers are always stored big-endian, so they're unaffected.
+    pub fn to_wire(&self, order: Endianness) -> Self
+    where
+        T: ConvertEndian,
+    {
+        let mut msg = self.clone();
+        msg.payload = msg.payload.convert_endian(order);
+        msg
+    }
+
+    /// Returns a copy of a message received from the flight software (say, with [`Self::from_bytes`])
+    /// with its payload converted from the byte order `order` used by the flight software to host byte order.
+    ///
+    /// Headers are always stored big-endian, so they're unaffected.
+    pub fn to_host(&self, order: Endianness) -> Self
+    where
+        T: ConvertEndian,
+    {
+        // converting is its own inverse
+        self.to_wire(order)
+    }
+
+    /// Returns the message's CCSDS primary header.
+    pub fn primary_header(&self) -> &PrimaryHeader {
+        &self.primary
+    }
+
+    /// Returns the message's CCSDS primary header for modification.
+    ///
+    /// Note that this bypasses the checks done when the message ID and other fields are set;
+    /// it's on the caller to keep the header consistent with the rest of the message.
+    pub fn primary_header_mut(&mut self) -> &mut PrimaryHeader {
+        &mut self.primary
+    }
+
+    /// Returns the message's secondary header.
+    pub fn secondary_header(&self) -> &H {
+        &self.secondary
+    }
+
+    /// Returns the message's secondary header for modification.
+    ///
+    /// As with [`Self::primary_header_mut`],
+    /// it's on the caller to keep the header consistent with the rest of the message.
+    pub fn secondary_header_mut(&mut self) -> &mut H {
+        &mut self.secondary
+    }
+
+    /// Returns the message's application process identifier.
+    pub fn apid(&self) -> u16 {
+        self.primary.apid()
+    }
+
+    /// Returns the message's packet type.
+    pub fn packet_type(&self) -> PacketType {
+        self.primary.packet_type()
+    }
+
+    /// Returns the message's sequence number.
+    pub fn sequence_number(&self) -> u16 {
+        self.primary.sequence_count()
+    }
+
+    /// If `sequence_number` fits in 14 bits, sets the message's sequence number to `sequence_number`.
+    pub fn set_sequence_number(&mut self, sequence_number: u16) -> Result<(), Error> {
+        self.primary.set_sequence_count(sequence_number)
+    }
+
+    /// Increment the message's sequence number.
+    pub fn increment_sequence_num(&mut self) {
+        self.primary.increment_sequence_count();
+    }
+}
+
+/// Does the checks of [`SecondaryHeader::validate`]'s default implementation
+/// on a packet with the secondary header `H`.
+pub(crate) fn check_space_packet<H: SecondaryHeader>(bytes: &[u8]) -> Result<(), Error> {
+    let header = crate::raw::primary_header(bytes);
+
+    if header.version() != 0 {
+        return Err(Error::InvalidVersion(header.version()));
+    }
+    if header.has_secondary_header() != (size_of::<H>() != 0) {
+        return Err(Error::InvalidSecondaryHeaderFlag(bytes[0]));
+    }
+    if header.packet_len() != bytes.len() {
+        return Err(Error::LengthFieldMismatch {
+            expected: bytes.len(),
+            actual: header.packet_len(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(transparent)]
+    #[derive(Clone, Copy)]
+    struct TestHeader([u8; 3]);
+
+    // Safety: a TestHeader is just three bytes.
+    unsafe impl AnyBitPattern for TestHeader {}
+    unsafe impl NoPadding for TestHeader {}
+
+    impl SecondaryHeader for TestHeader {}
+
+    #[test]
+    fn from_parts_sets_length_from_wire_size() {
+        let mut primary = PrimaryHeader::default();
+        primary.set_apid(0x55).unwrap();
+        let packet =
+            SpacePacket::from_parts(primary, TestHeader([0xA5; 3]), 0xBEEFu16.to_be_bytes())
+                .unwrap();
+
+        assert_eq!(SpacePacket::<TestHeader, [u8; 2]>::LEN, 11);
+        assert_eq!(packet.primary_header().packet_len(), 11);
+        assert!(packet.primary_header().has_secondary_header());
+        assert_eq!(
+            packet.as_bytes(),
+            [0x08, 0x55, 0x00, 0x00, 0x00, 0x04, 0xA5, 0xA5, 0xA5, 0xBE, 0xEF]
+        );
+    }
+
+    #[test]
+    fn from_bytes_round_trips_and_checks_headers() {
+        let packet = SpacePacket::from_parts(PrimaryHeader::default(), (), [1u8, 2, 3]).unwrap();
+        let bytes = packet.as_bytes();
+
+        let parsed = SpacePacket::<(), [u8; 3]>::from_bytes(bytes).unwrap();
+        assert_eq!(parsed.payload, [1, 2, 3]);
+        assert!(!parsed.primary_header().has_secondary_header());
+
+        assert_eq!(
+            SpacePacket::<(), [u8; 2]>::from_bytes(bytes).err(),
+            Some(Error::WrongLength {
+                expected: 8,
+                actual: 9
+            })
+        );
+
+        let mut flagged = bytes.to_vec();
+        flagged[0] |= 0x08;
+        assert_eq!(
+            SpacePacket::<(), [u8; 3]>::from_bytes(&flagged).err(),
+            Some(Error::InvalidSecondaryHeaderFlag(0x08))
+        );
+    }
+}
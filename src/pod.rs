@@ -0,0 +1,256 @@
+use core::mem::size_of;
+
+use crate::{ConvertEndian, Endianness, Error};
+
+/// Marker trait for types for which every sequence of `size_of::<Self>()` bytes is a valid value,
+/// so that they can be safely read out of a received packet.
+///
+/// This is implemented for the primitive integer and floating-point types, and arrays of them.
+/// For payload structs, the easiest way to implement it is to declare the struct with [`payload!`](crate::payload),
+/// which checks each field's type.
+///
+/// # Safety
+///
+/// Implementing this trait is only sound if, for every sequence of `size_of::<Self>()` bytes,
+/// reinterpreting those bytes as a `Self` produces a valid `Self`.
+/// In particular, `Self` must not contain references, pointers, `bool`s, `char`s,
+/// enums, or any other type with invalid bit patterns,
+/// and if `Self` is a struct it should be `#[repr(C)]` or `#[repr(transparent)]`
+/// with every field itself being `AnyBitPattern`.
+pub unsafe trait AnyBitPattern: Copy + 'static {}
+
+/// Marker trait for types that contain no padding bytes,
+/// so that viewing a value as a sequence of bytes never reads uninitialized memory.
+///
+/// This is implemented for the primitive integer, floating-point, `bool` and `char` types, and arrays of them.
+/// For payload structs, the easiest way to implement it is to declare the struct with [`payload!`](crate::payload),
+/// which checks each field's type and the struct's size.
+///
+/// # Safety
+///
+/// Implementing this trait is only sound if every byte of every value of `Self` is initialized:
+/// if `Self` is a struct, it should be `#[repr(C)]` or `#[repr(transparent)]`,
+/// every field must itself be `NoPadding`,
+/// and `size_of::<Self>()` must be the sum of the sizes of the fields.
+pub unsafe trait NoPadding: Copy + 'static {}
+
+/// Describes one field of a [`Payload`] struct, for use in decoding and displaying payloads generically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldInfo {
+    /// The field's name (for tuple structs, its index).
+    pub name: &'static str,
+
+    /// The field's offset from the start of the payload, in bytes.
+    pub offset: usize,
+
+    /// The field's size, in bytes.
+    pub size: usize,
+
+    /// The field's type, as written in the struct definition.
+    pub type_name: &'static str,
+
+    /// The field's wire byte order, if it was fixed with a `#[payload(endian = ...)]` attribute.
+    pub endianness: Option<Endianness>,
+}
+
+impl FieldInfo {
+    /// Returns this field's bytes, given the bytes of a whole payload,
+    /// or `None` if `payload` is too short to contain the field.
+    pub fn bytes<'a>(&self, payload: &'a [u8]) -> Option<&'a [u8]> {
+        payload.get(self.offset..self.offset + self.size)
+    }
+}
+
+/// A payload struct whose layout has been checked to be safe to send and receive as bytes,
+/// along with a description of its fields.
+///
+/// Implement this with `#[derive(Payload)]` (with the `derive` feature enabled)
+/// or by declaring the struct with [`payload!`](crate::payload).
+pub trait Payload: AnyBitPattern + NoPadding + ConvertEndian {
+    /// The payload's fields, in declaration order.
+    const FIELDS: &'static [FieldInfo];
+
+    /// Returns a view of the payload as a sequence of bytes.
+    fn as_bytes(&self) -> &[u8] {
+        // Safety: Self has no padding, and we're using the lifetime of an immutable ref to self.
+        unsafe { core::slice::from_raw_parts(self as *const Self as *const u8, size_of::<Self>()) }
+    }
+
+    /// Copies `bytes` into a new payload, if `bytes` is exactly as long as the payload.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != size_of::<Self>() {
+            return Err(Error::WrongLength {
+                expected: size_of::<Self>(),
+                actual: bytes.len(),
+            });
+        }
+
+        // Safety: bytes is the right length, and every byte pattern is a valid Self.
+        Ok(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
+    }
+}
+
+macro_rules! impl_any_bit_pattern {
+    ($($ty:ty),*) => {
+        $(
+            // Safety: every bit pattern is a valid value of a primitive integer or float.
+            unsafe impl AnyBitPattern for $ty {}
+
+            // Safety: primitive integers and floats have no padding.
+            unsafe impl NoPadding for $ty {}
+        )*
+    };
+}
+
+impl_any_bit_pattern!(u8, u16, u32, u64, u128, usize);
+impl_any_bit_pattern!(i8, i16, i32, i64, i128, isize);
+impl_any_bit_pattern!(f32, f64, ());
+
+// Safety: bool and char have no padding.
+unsafe impl NoPadding for bool {}
+unsafe impl NoPadding for char {}
+
+// Safety: an array has no padding between elements, and every element can have any bit pattern.
+unsafe impl<T: AnyBitPattern, const N: usize> AnyBitPattern for [T; N] {}
+
+// Safety: an array has no padding between elements, and no element has padding.
+unsafe impl<T: NoPadding, const N: usize> NoPadding for [T; N] {}
+
+/// Declares a `#[repr(C)]` payload struct and implements [`AnyBitPattern`](crate::AnyBitPattern)
+/// and [`NoPadding`](crate::NoPadding) for it, checking at compile time that
+/// every field's type is itself `AnyBitPattern` and `NoPadding`, and that the struct has no padding.
+/// It also implements [`ConvertEndian`](crate::ConvertEndian) field by field,
+/// and [`Payload`](crate::Payload).
+///
+/// This is the declarative counterpart of `#[derive(Payload)]`, for when the `derive` feature is off.
+///
+/// If the compiler would need to insert padding between fields (or at the end of the struct),
+/// this fails to compile; add explicit spare fields to fill the gaps:
+///
+/// ```
+/// ccsds_packet::payload! {
+///     #[derive(Clone, Copy)]
+///     struct Padded {
+///         a: u8,
+///         spare: [u8; 3],
+///         b: u32,
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail,E0080
+/// ccsds_packet::payload! {
+///     #[derive(Clone, Copy)]
+///     struct Padded {
+///         a: u8,
+///         b: u32,
+///     }
+/// }
+/// ```
+///
+/// The struct must derive (or otherwise implement) [`Clone`] and [`Copy`].
+/// The macro adds `#[repr(C)]` itself, so don't add another `repr` attribute.
+#[macro_export]
+macro_rules! payload {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        // Safety: the struct is repr(C), and each field's type is checked to be AnyBitPattern below.
+        unsafe impl $crate::AnyBitPattern for $name {}
+
+        // Safety: the struct is repr(C), each field's type is checked to be NoPadding below,
+        // and the struct's size is checked to be the sum of its fields' sizes.
+        unsafe impl $crate::NoPadding for $name {}
+
+        impl $crate::ConvertEndian for $name {
+            fn convert_endian(self, order: $crate::Endianness) -> Self {
+                Self {
+                    $( $field: $crate::ConvertEndian::convert_endian(self.$field, order), )*
+                }
+            }
+        }
+
+        impl $crate::Payload for $name {
+            const FIELDS: &'static [$crate::FieldInfo] = &[
+                $(
+                    $crate::FieldInfo {
+                        name: ::core::stringify!($field),
+                        offset: ::core::mem::offset_of!($name, $field),
+                        size: ::core::mem::size_of::<$ty>(),
+                        type_name: ::core::stringify!($ty),
+                        endianness: ::core::option::Option::None,
+                    },
+                )*
+            ];
+        }
+
+        const _: () = {
+            const fn assert_pod<T: $crate::AnyBitPattern + $crate::NoPadding>() {}
+            $( assert_pod::<$ty>(); )*
+
+            ::core::assert!(
+                ::core::mem::size_of::<$name>() == 0 $( + ::core::mem::size_of::<$ty>() )*,
+                ::core::concat!("`", ::core::stringify!($name), "` contains padding"),
+            );
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, CommandMsgId, Endianness};
+
+    crate::payload! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Housekeeping {
+            mode: u8,
+            spare: u8,
+            voltage: u16,
+            counter: u32,
+        }
+    }
+
+    #[test]
+    fn payload_structs_go_through_packets() {
+        let fields = Housekeeping::FIELDS;
+        assert_eq!(fields.len(), 4);
+        assert_eq!(
+            (fields[2].name, fields[2].offset, fields[2].size),
+            ("voltage", 2, 2)
+        );
+        assert_eq!(fields[3].type_name, "u32");
+
+        let payload = Housekeeping {
+            mode: 1,
+            spare: 0,
+            voltage: 0x0203,
+            counter: 0x0405_0607,
+        };
+        let mut cmd = Command::new(CommandMsgId::new(0x1880), 3, payload)
+            .unwrap()
+            .to_wire(Endianness::Big);
+        let bytes = cmd.as_bytes_with_checksum().to_vec();
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(&bytes[8..], [1, 0, 2, 3, 4, 5, 6, 7]);
+
+        let parsed = Command::<Housekeeping>::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.as_bytes(), bytes.as_slice());
+        assert_eq!(parsed.to_host(Endianness::Big).payload, payload);
+    }
+}
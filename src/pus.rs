@@ -0,0 +1,561 @@
+//! Packets following the ECSS Packet Utilisation Standard (ECSS-E-ST-70-41C, "PUS-C"),
+//! with PUS telecommand and telemetry secondary headers and a CRC-16 packet error control field.
+
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+use crate::packet::check_space_packet;
+use crate::raw::primary_header;
+use crate::{
+    AnyBitPattern, ConvertEndian, Cuc, Endianness, Error, NoPadding, PacketType, PrimaryHeader,
+    SecondaryHeader, SequenceFlags, SpacePacket, TimeConfig, TimeFormat, TimeSource, UtcTime,
+};
+
+/// The PUS version number of PUS-C packets.
+const PUS_VERSION: u8 = 2;
+
+/// The length of the fields of the PUS telemetry secondary header before the time field, in bytes.
+const TM_FIELDS_LEN: usize = 7;
+
+/// Computes the CRC-16 of `bytes` used for the PUS packet error control field:
+/// the CCITT polynomial (`0x1021`), starting from `0xFFFF`.
+///
+/// For a packet whose packet error control field holds a valid CRC,
+/// the CRC of the whole packet is zero.
+pub fn crc16_ccitt(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        let mut crc = crc ^ ((byte as u16) << 8);
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// The PUS-C telecommand secondary header, stored in its on-the-wire representation.
+///
+/// Packets with this header end in a packet error control field (see [`WithPec`]),
+/// which [`SpacePacket::from_bytes`] checks.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PusTcHeader([u8; 5]);
+
+// Safety: a PusTcHeader is just five bytes, any of which may have any value.
+unsafe impl AnyBitPattern for PusTcHeader {}
+unsafe impl NoPadding for PusTcHeader {}
+
+impl SecondaryHeader for PusTcHeader {
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_pus_packet::<Self>(bytes, PacketType::Command)
+    }
+
+    fn validate_packet(bytes: &[u8]) -> Result<(), Error> {
+        check_pec(bytes)
+    }
+}
+
+impl PusTcHeader {
+    /// The length of the header, in bytes.
+    pub const LEN: usize = 5;
+
+    /// The acknowledgement flag requesting a report of successful acceptance of the telecommand.
+    pub const ACK_ACCEPTANCE: u8 = 0b0001;
+
+    /// The acknowledgement flag requesting a report of successful start of execution.
+    pub const ACK_START: u8 = 0b0010;
+
+    /// The acknowledgement flag requesting reports of successful progress of execution.
+    pub const ACK_PROGRESS: u8 = 0b0100;
+
+    /// The acknowledgement flag requesting a report of successful completion of execution.
+    pub const ACK_COMPLETION: u8 = 0b1000;
+
+    /// Returns a header for a telecommand of the message type (`service_type`, `message_subtype`),
+    /// with no acknowledgements requested and a source ID of 0.
+    pub const fn new(service_type: u8, message_subtype: u8) -> Self {
+        Self([PUS_VERSION << 4, service_type, message_subtype, 0, 0])
+    }
+
+    /// Returns the 4-bit PUS version number, which is 2 for PUS-C.
+    pub const fn pus_version(&self) -> u8 {
+        self.0[0] >> 4
+    }
+
+    /// Returns the 4-bit acknowledgement flags (see [`Self::ACK_ACCEPTANCE`] and the like).
+    pub const fn ack_flags(&self) -> u8 {
+        self.0[0] & 0x0F
+    }
+
+    /// If `flags` fits in 4 bits, sets the acknowledgement flags to `flags`.
+    pub fn set_ack_flags(&mut self, flags: u8) -> Result<(), Error> {
+        if flags > 0x0F {
+            return Err(Error::InvalidAckFlags(flags));
+        }
+        self.0[0] = (self.0[0] & 0xF0) | flags;
+        Ok(())
+    }
+
+    /// Returns the service type ID.
+    pub const fn service_type(&self) -> u8 {
+        self.0[1]
+    }
+
+    /// Sets the service type ID.
+    pub fn set_service_type(&mut self, service_type: u8) {
+        self.0[1] = service_type;
+    }
+
+    /// Returns the message subtype ID.
+    pub const fn message_subtype(&self) -> u8 {
+        self.0[2]
+    }
+
+    /// Sets the message subtype ID.
+    pub fn set_message_subtype(&mut self, message_subtype: u8) {
+        self.0[2] = message_subtype;
+    }
+
+    /// Returns the source ID: the application process that sent the telecommand.
+    pub const fn source_id(&self) -> u16 {
+        ((self.0[3] as u16) << 8) | (self.0[4] as u16)
+    }
+
+    /// Sets the source ID.
+    pub fn set_source_id(&mut self, source_id: u16) {
+        self.0[3..5].copy_from_slice(&source_id.to_be_bytes());
+    }
+}
+
+/// The PUS-C telemetry secondary header, stored in its on-the-wire representation,
+/// with its time field in the time format `F` (by default, a CUC time code with an implicit P-field).
+///
+/// Packets with this header end in a packet error control field (see [`WithPec`]),
+/// which [`SpacePacket::from_bytes`] checks.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct PusTmHeader<F: TimeFormat = Cuc<4, 2>> {
+    fields: [u8; TM_FIELDS_LEN],
+    time: F::Header,
+    time_format: PhantomData<F>,
+}
+
+// Safety: PusTmHeader is packed, so there's no padding,
+// and its non-zero-sized fields are AnyBitPattern and NoPadding.
+unsafe impl<F: TimeFormat> AnyBitPattern for PusTmHeader<F> {}
+unsafe impl<F: TimeFormat> NoPadding for PusTmHeader<F> {}
+
+impl<F: TimeFormat> SecondaryHeader for PusTmHeader<F> {
+    fn validate(bytes: &[u8]) -> Result<(), Error> {
+        check_pus_packet::<Self>(bytes, PacketType::Telemetry)
+    }
+
+    fn validate_packet(bytes: &[u8]) -> Result<(), Error> {
+        check_pec(bytes)
+    }
+}
+
+impl<F: TimeFormat> PusTmHeader<F> {
+    /// The length of the header, in bytes.
+    pub const LEN: usize = size_of::<Self>();
+
+    /// Returns a header for a telemetry message of the message type (`service_type`, `message_subtype`),
+    /// with the other fields (the time included) zeroed.
+    pub fn new(service_type: u8, message_subtype: u8) -> Self {
+        Self {
+            fields: [PUS_VERSION << 4, service_type, message_subtype, 0, 0, 0, 0],
+            time: F::Header::default(),
+            time_format: PhantomData,
+        }
+    }
+
+    /// Returns the 4-bit PUS version number, which is 2 for PUS-C.
+    pub fn pus_version(&self) -> u8 {
+        self.fields[0] >> 4
+    }
+
+    /// Returns the 4-bit spacecraft time reference status.
+    pub fn time_reference_status(&self) -> u8 {
+        self.fields[0] & 0x0F
+    }
+
+    /// If `status` fits in 4 bits, sets the spacecraft time reference status to `status`.
+    pub fn set_time_reference_status(&mut self, status: u8) -> Result<(), Error> {
+        if status > 0x0F {
+            return Err(Error::InvalidTimeReferenceStatus(status));
+        }
+        self.fields[0] = (self.fields[0] & 0xF0) | status;
+        Ok(())
+    }
+
+    /// Returns the service type ID.
+    pub fn service_type(&self) -> u8 {
+        self.fields[1]
+    }
+
+    /// Sets the service type ID.
+    pub fn set_service_type(&mut self, service_type: u8) {
+        self.fields[1] = service_type;
+    }
+
+    /// Returns the message subtype ID.
+    pub fn message_subtype(&self) -> u8 {
+        self.fields[2]
+    }
+
+    /// Sets the message subtype ID.
+    pub fn set_message_subtype(&mut self, message_subtype: u8) {
+        self.fields[2] = message_subtype;
+    }
+
+    /// Returns the message type counter,
+    /// which counts the messages of this type sent to the destination.
+    pub fn message_type_counter(&self) -> u16 {
+        u16::from_be_bytes([self.fields[3], self.fields[4]])
+    }
+
+    /// Sets the message type counter.
+    pub fn set_message_type_counter(&mut self, counter: u16) {
+        self.fields[3..5].copy_from_slice(&counter.to_be_bytes());
+    }
+
+    /// Returns the destination ID: the application process the telemetry is addressed to.
+    pub fn destination_id(&self) -> u16 {
+        u16::from_be_bytes([self.fields[5], self.fields[6]])
+    }
+
+    /// Sets the destination ID.
+    pub fn set_destination_id(&mut self, destination_id: u16) {
+        self.fields[5..7].copy_from_slice(&destination_id.to_be_bytes());
+    }
+
+    /// Returns the time, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        // the time field may be unaligned, so work on a copy
+        let time = self.time;
+        F::timestamp(time.as_ref())
+    }
+
+    /// Sets the time to `timestamp`, given in the form used by the time format `F`.
+    pub fn set_timestamp_to(&mut self, timestamp: F::Timestamp) {
+        let mut time = self.time;
+        F::set_timestamp(time.as_mut(), timestamp);
+        self.time = time;
+    }
+}
+
+/// A payload followed by the two-byte packet error control field, which holds a CRC of the whole packet.
+///
+/// This is laid out without any alignment or padding, so it can follow a secondary header of any length;
+/// as its fields may be unaligned, the payload is read and written by value.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct WithPec<T: Copy> {
+    data: T,
+    pec: [u8; 2],
+}
+
+// Safety: WithPec is packed, so there's no padding, and both fields can have any bit pattern if T can.
+unsafe impl<T: AnyBitPattern> AnyBitPattern for WithPec<T> {}
+
+// Safety: WithPec is packed, so there's no padding besides any within T.
+unsafe impl<T: NoPadding> NoPadding for WithPec<T> {}
+
+impl<T: ConvertEndian> ConvertEndian for WithPec<T> {
+    fn convert_endian(self, order: Endianness) -> Self {
+        // the packet error control field is always big-endian
+        Self {
+            data: { self.data }.convert_endian(order),
+            pec: self.pec,
+        }
+    }
+}
+
+impl<T: Copy> WithPec<T> {
+    /// Wraps `data`, with the packet error control field zeroed.
+    pub fn new(data: T) -> Self {
+        Self { data, pec: [0; 2] }
+    }
+
+    /// Returns a copy of the payload.
+    pub fn data(&self) -> T {
+        self.data
+    }
+
+    /// Sets the payload to `data`.
+    pub fn set_data(&mut self, data: T) {
+        self.data = data;
+    }
+
+    /// Returns the contents of the packet error control field.
+    pub fn pec(&self) -> u16 {
+        u16::from_be_bytes(self.pec)
+    }
+}
+
+/// A PUS-C telecommand packet, as a Rust structure.
+pub type PusTc<T> = SpacePacket<PusTcHeader, WithPec<T>>;
+
+/// A PUS-C telemetry packet, as a Rust structure, whose time field is in the time format `F`.
+pub type PusTmPacket<T, F> = SpacePacket<PusTmHeader<F>, WithPec<T>>;
+
+/// A PUS-C telemetry packet, as a Rust structure, with a 4-octet coarse, 2-octet fine CUC time field.
+pub type PusTm<T> = PusTmPacket<T, Cuc<4, 2>>;
+
+impl<H: SecondaryHeader, T: Copy> SpacePacket<H, WithPec<T>> {
+    /// Returns a copy of the message's payload.
+    pub fn data(&self) -> T {
+        self.payload.data()
+    }
+
+    /// Sets the message's payload to `data`.
+    ///
+    /// The packet error control field is not updated; see [`Self::set_pec`].
+    pub fn set_data(&mut self, data: T) {
+        self.payload.set_data(data);
+    }
+
+    /// Returns the contents of the message's packet error control field.
+    pub fn pec(&self) -> u16 {
+        self.payload.pec()
+    }
+
+    /// Computes the CRC the message's packet error control field should contain,
+    /// given the rest of the message's contents.
+    pub fn compute_pec(&self) -> u16
+    where
+        T: NoPadding,
+    {
+        let bytes = self.as_bytes();
+        crc16_ccitt(&bytes[..bytes.len() - 2])
+    }
+
+    /// Sets the message's packet error control field to the CRC of the rest of the message.
+    ///
+    /// The CRC is not kept up to date automatically:
+    /// call this again after modifying the headers or payload.
+    pub fn set_pec(&mut self)
+    where
+        T: NoPadding,
+    {
+        self.payload.pec = self.compute_pec().to_be_bytes();
+    }
+
+    /// Updates the packet error control field (see [`Self::set_pec`]),
+    /// then returns a view of the message as a sequence of bytes, ready for transmission.
+    pub fn as_bytes_with_pec(&mut self) -> &[u8]
+    where
+        T: NoPadding,
+    {
+        self.set_pec();
+        self.as_bytes()
+    }
+
+    /// Returns whether the message's packet error control field is consistent with its contents.
+    pub fn is_pec_valid(&self) -> bool
+    where
+        T: NoPadding,
+    {
+        crc16_ccitt(self.as_bytes()) == 0
+    }
+
+    /// Returns an error describing the discrepancy
+    /// if the message's packet error control field is inconsistent with its contents.
+    pub fn validate_pec(&self) -> Result<(), Error>
+    where
+        T: NoPadding,
+    {
+        check_pec(self.as_bytes())
+    }
+
+    /// Turns a sequence of bytes representing a message into a packet,
+    /// as in [`Self::from_bytes`], but without checking the packet error control field.
+    pub fn from_bytes_ignoring_pec(bytes: &[u8]) -> Result<Self, Error>
+    where
+        T: AnyBitPattern,
+    {
+        // Safety: every byte pattern is a valid WithPec<T>.
+        unsafe { Self::from_bytes_unchecked(bytes) }
+    }
+}
+
+impl<T: Copy + NoPadding> PusTc<T> {
+    /// Returns a new telecommand from the application process `apid`,
+    /// of the message type (`service_type`, `message_subtype`), carrying `data`,
+    /// with its packet error control field set.
+    ///
+    /// Fails if `apid` is out of range, or if the payload is too large to fit in a packet.
+    pub fn new(apid: u16, service_type: u8, message_subtype: u8, data: T) -> Result<Self, Error> {
+        let primary = pus_primary_header(apid, PacketType::Command)?;
+        let secondary = PusTcHeader::new(service_type, message_subtype);
+
+        let mut tc = Self::from_parts(primary, secondary, WithPec::new(data))?;
+        tc.set_pec();
+        Ok(tc)
+    }
+}
+
+impl<T: Copy + NoPadding, F: TimeFormat> PusTmPacket<T, F> {
+    /// Returns a new telemetry message from the application process `apid`,
+    /// of the message type (`service_type`, `message_subtype`), carrying `data`,
+    /// with its packet error control field set.
+    ///
+    /// The other secondary header fields (the time included) start out zeroed.
+    /// Fails if `apid` is out of range, or if the payload is too large to fit in a packet.
+    pub fn new(apid: u16, service_type: u8, message_subtype: u8, data: T) -> Result<Self, Error> {
+        let primary = pus_primary_header(apid, PacketType::Telemetry)?;
+        let secondary = PusTmHeader::new(service_type, message_subtype);
+
+        let mut tm = Self::from_parts(primary, secondary, WithPec::new(data))?;
+        tm.set_pec();
+        Ok(tm)
+    }
+
+    /// Returns the message's time, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        self.secondary_header().timestamp()
+    }
+
+    /// Returns the message's time as a UTC time,
+    /// taking the spacecraft clock to be configured as in `config`.
+    pub fn timestamp_utc(&self, config: &TimeConfig) -> UtcTime {
+        config.to_utc(F::to_duration(self.timestamp()))
+    }
+
+    /// Sets the message's time to
+    /// `seconds` seconds + `nanoseconds` nanoseconds
+    /// since the spacecraft clock's epoch, rounded down to the resolution of the time format `F`.
+    ///
+    /// The packet error control field is not updated; see [`Self::set_pec`].
+    pub fn set_timestamp(&mut self, seconds: u64, nanoseconds: u32) {
+        self.set_timestamp_to(F::from_secs_nanos(seconds, nanoseconds));
+    }
+
+    /// Sets the message's time to `timestamp`, given in the form used by the time format `F`.
+    ///
+    /// The packet error control field is not updated; see [`Self::set_pec`].
+    pub fn set_timestamp_to(&mut self, timestamp: F::Timestamp) {
+        self.secondary_header_mut().set_timestamp_to(timestamp);
+    }
+
+    /// Sets the message's time to the current time according to `source`.
+    ///
+    /// The packet error control field is not updated; see [`Self::set_pec`].
+    pub fn timestamp_with<S: TimeSource + ?Sized>(&mut self, source: &S) -> Result<(), Error> {
+        let since_epoch = source.now()?;
+
+        self.set_timestamp(since_epoch.as_secs(), since_epoch.subsec_nanos());
+        Ok(())
+    }
+}
+
+/// Checks that the packet error control field at the end of `bytes`, a whole packet,
+/// holds the CRC of the rest of the packet.
+fn check_pec(bytes: &[u8]) -> Result<(), Error> {
+    if crc16_ccitt(bytes) == 0 {
+        return Ok(());
+    }
+
+    let (rest, pec) = bytes.split_at(bytes.len() - 2);
+    Err(Error::PecMismatch {
+        expected: crc16_ccitt(rest),
+        actual: u16::from_be_bytes([pec[0], pec[1]]),
+    })
+}
+
+/// Builds the primary header of an unsegmented PUS packet from the application process `apid`,
+/// less its secondary header flag and length.
+fn pus_primary_header(apid: u16, packet_type: PacketType) -> Result<PrimaryHeader, Error> {
+    let mut primary = PrimaryHeader::default();
+    primary.set_apid(apid)?;
+    primary.set_packet_type(packet_type);
+    primary.set_sequence_flags(SequenceFlags::Unsegmented);
+    Ok(primary)
+}
+
+/// Does the sanity checks on a PUS packet's headers: those of [`SecondaryHeader::validate`]'s default,
+/// plus checking that the packet type is `packet_type`, that the packet is unsegmented,
+/// and that the PUS version number is that of PUS-C.
+fn check_pus_packet<H: SecondaryHeader>(
+    bytes: &[u8],
+    packet_type: PacketType,
+) -> Result<(), Error> {
+    check_space_packet::<H>(bytes)?;
+
+    let header = primary_header(bytes);
+    if header.packet_type() != packet_type {
+        return Err(Error::InvalidVersionOrType(bytes[0]));
+    }
+    if header.sequence_flags() != SequenceFlags::Unsegmented {
+        return Err(Error::InvalidSequenceFlags(bytes[2]));
+    }
+
+    let pus_version = bytes[PrimaryHeader::LEN] >> 4;
+    if pus_version != PUS_VERSION {
+        return Err(Error::InvalidPusVersion(pus_version));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_known_answer() {
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+        assert_eq!(crc16_ccitt(b""), 0xFFFF);
+
+        let mut bytes = b"123456789".to_vec();
+        bytes.extend_from_slice(&0x29B1u16.to_be_bytes());
+        assert_eq!(crc16_ccitt(&bytes), 0);
+    }
+
+    #[test]
+    fn parsing_checks_pec() {
+        let mut tc = PusTc::new(0x123, 17, 1, [0xABu8, 0xCD]).unwrap();
+        let mut bytes = tc.as_bytes_with_pec().to_vec();
+        assert_eq!(
+            bytes[..13],
+            [0x19, 0x23, 0xC0, 0x00, 0x00, 0x08, 0x20, 17, 1, 0, 0, 0xAB, 0xCD]
+        );
+
+        let parsed = PusTc::<[u8; 2]>::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.data(), [0xAB, 0xCD]);
+        assert_eq!(parsed.pec(), tc.pec());
+
+        bytes[12] ^= 0x01;
+        assert_eq!(
+            PusTc::<[u8; 2]>::from_bytes(&bytes).err(),
+            Some(Error::PecMismatch {
+                expected: crc16_ccitt(&bytes[..13]),
+                actual: tc.pec(),
+            })
+        );
+
+        let unchecked = PusTc::<[u8; 2]>::from_bytes_ignoring_pec(&bytes).unwrap();
+        assert_eq!(unchecked.data(), [0xAB, 0xCC]);
+        assert!(!unchecked.is_pec_valid());
+    }
+
+    #[test]
+    fn parsing_checks_pus_headers() {
+        let mut tm = PusTm::new(0x42, 3, 25, 7u32.to_be_bytes()).unwrap();
+        let bytes = tm.as_bytes_with_pec().to_vec();
+        assert!(PusTm::<[u8; 4]>::from_bytes(&bytes).is_ok());
+
+        // a telemetry packet isn't a telecommand, even if it's the right length
+        assert!(PusTc::<[u8; 10]>::from_bytes(&bytes[..PusTc::<[u8; 10]>::LEN]).is_err());
+
+        let mut bad_version = bytes;
+        bad_version[PrimaryHeader::LEN] = 0x10;
+        assert_eq!(
+            PusTm::<[u8; 4]>::from_bytes_ignoring_pec(&bad_version).err(),
+            Some(Error::InvalidPusVersion(1))
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Borrowed views of CCSDS space packets of any kind, cFS or not,
+//! as carved out of a byte stream or buffer, and iteration over back-to-back packets.
+
+use core::iter::FusedIterator;
+
+use crate::{Error, PrimaryHeader};
+
+/// A borrowed view of a byte buffer holding exactly one CCSDS space packet:
+/// a primary header, then the packet data field (any secondary header and the user data).
+///
+/// To get at cFS headers, pass [`Self::as_bytes`] to a view such as [`TelemetryRef::new`](crate::TelemetryRef::new).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RawPacket<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> RawPacket<'a> {
+    /// If `bytes` holds exactly one space packet with a version number of 0,
+    /// and the packet data length field agrees with its length, returns a view of it.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() <= PrimaryHeader::LEN {
+            return Err(Error::Truncated {
+                expected: PrimaryHeader::LEN + 1,
+                actual: bytes.len(),
+            });
+        }
+
+        let header = primary_header(bytes);
+        if header.version() != 0 {
+            return Err(Error::InvalidVersion(header.version()));
+        }
+        if header.packet_len() != bytes.len() {
+            return Err(Error::LengthFieldMismatch {
+                expected: bytes.len(),
+                actual: header.packet_len(),
+            });
+        }
+
+        Ok(Self::new_unchecked(bytes))
+    }
+
+    /// Wraps `bytes`, which must already have been checked to hold a space packet.
+    pub(crate) fn new_unchecked(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Returns the whole packet as a sequence of bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the packet's CCSDS primary header.
+    pub fn primary_header(&self) -> &'a PrimaryHeader {
+        primary_header(self.bytes)
+    }
+
+    /// Returns the packet data field: everything following the primary header.
+    pub fn data(&self) -> &'a [u8] {
+        &self.bytes[PrimaryHeader::LEN..]
+    }
+
+    /// Returns whether this is an idle packet, there only to fill link capacity.
+    pub fn is_idle(&self) -> bool {
+        self.primary_header().is_idle()
+    }
+}
+
+/// An iterator over back-to-back space packets in a buffer, such as a recorded file
+/// or a datagram carrying several packets.
+///
+/// Each packet's length is taken from its packet data length field.
+/// If the rest of the buffer is too short to hold the next packet,
+/// or the next packet's version number isn't 0 (so its header probably isn't one),
+/// the iterator yields an error and then stops; [`Self::remainder`] gives the bytes it didn't get through.
+/// Idle packets are yielded like any other unless [`Self::skip_idle`] is used.
+#[derive(Clone, Debug)]
+pub struct PacketIter<'a> {
+    bytes: &'a [u8],
+    failed: bool,
+    skip_idle: bool,
+}
+
+impl<'a> PacketIter<'a> {
+    /// Creates an iterator over the packets in `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            failed: false,
+            skip_idle: false,
+        }
+    }
+
+    /// Passes over idle packets instead of yielding them.
+    pub fn skip_idle(mut self) -> Self {
+        self.skip_idle = true;
+        self
+    }
+
+    /// Returns the bytes not yet yielded as packets,
+    /// including those of a packet the iterator stopped at because of an error.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+impl<'a> Iterator for PacketIter<'a> {
+    type Item = Result<RawPacket<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.bytes.is_empty() && !self.failed {
+            let packet_len = match next_packet_len(self.bytes) {
+                Ok(packet_len) => packet_len,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            };
+
+            let (packet, rest) = self.bytes.split_at(packet_len);
+            self.bytes = rest;
+
+            let packet = RawPacket::new_unchecked(packet);
+            if !(self.skip_idle && packet.is_idle()) {
+                return Some(Ok(packet));
+            }
+        }
+
+        None
+    }
+}
+
+impl FusedIterator for PacketIter<'_> {}
+
+/// Returns the length of the packet at the start of `bytes`, checking that it's all there.
+fn next_packet_len(bytes: &[u8]) -> Result<usize, Error> {
+    if bytes.len() < PrimaryHeader::LEN {
+        return Err(Error::Truncated {
+            expected: PrimaryHeader::LEN,
+            actual: bytes.len(),
+        });
+    }
+
+    let header = primary_header(bytes);
+    if header.version() != 0 {
+        return Err(Error::InvalidVersion(header.version()));
+    }
+    if bytes.len() < header.packet_len() {
+        return Err(Error::Truncated {
+            expected: header.packet_len(),
+            actual: bytes.len(),
+        });
+    }
+
+    Ok(header.packet_len())
+}
+
+/// Returns the primary header at the start of `bytes`, which must be at least [`PrimaryHeader::LEN`] bytes long.
+pub(crate) fn primary_header(bytes: &[u8]) -> &PrimaryHeader {
+    PrimaryHeader::from_bytes_ref(bytes[..PrimaryHeader::LEN].try_into().unwrap())
+}
+
+/// Returns the primary header at the start of `bytes`, which must be at least [`PrimaryHeader::LEN`] bytes long.
+pub(crate) fn primary_header_mut(bytes: &mut [u8]) -> &mut PrimaryHeader {
+    PrimaryHeader::from_bytes_mut((&mut bytes[..PrimaryHeader::LEN]).try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(apid: u16, data: &[u8]) -> Vec<u8> {
+        let mut header = PrimaryHeader::default();
+        header.set_apid(apid).unwrap();
+        header
+            .set_packet_len(PrimaryHeader::LEN + data.len())
+            .unwrap();
+
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn iterates_over_back_to_back_packets() {
+        let packets = [
+            packet(0x10, b"a"),
+            packet(0x11, b"bcd"),
+            packet(0x12, &[0; 50]),
+        ];
+        let stream = packets.concat();
+
+        let mut iter = PacketIter::new(&stream);
+        for expected in &packets {
+            let packet = iter.next().unwrap().unwrap();
+            assert_eq!(packet.as_bytes(), expected.as_slice());
+            assert_eq!(packet.data(), &expected[PrimaryHeader::LEN..]);
+        }
+        assert!(iter.next().is_none());
+        assert!(iter.remainder().is_empty());
+    }
+
+    #[test]
+    fn stops_after_a_truncated_packet() {
+        let whole = packet(0x10, b"whole");
+        let partial = packet(0x10, b"partial");
+        let stream = [whole.as_slice(), &partial[..9]].concat();
+
+        let mut iter = PacketIter::new(&stream);
+        assert_eq!(iter.next().unwrap().unwrap().as_bytes(), whole.as_slice());
+        assert_eq!(iter.remainder(), &partial[..9]);
+        assert_eq!(
+            iter.next(),
+            Some(Err(Error::Truncated {
+                expected: 13,
+                actual: 9
+            }))
+        );
+        assert!(iter.next().is_none());
+        assert_eq!(iter.remainder(), &partial[..9]);
+
+        // even a primary header needs all six bytes
+        let mut iter = PacketIter::new(&partial[..4]);
+        assert_eq!(
+            iter.next(),
+            Some(Err(Error::Truncated {
+                expected: 6,
+                actual: 4
+            }))
+        );
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn stops_at_a_bad_version_number() {
+        let good = packet(0x10, b"good");
+        let mut bad = packet(0x10, b"bad");
+        bad[0] |= 0x20;
+        let stream = [good.clone(), bad.clone(), good.clone()].concat();
+
+        let mut iter = PacketIter::new(&stream);
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next(), Some(Err(Error::InvalidVersion(1))));
+        assert!(iter.next().is_none());
+        assert_eq!(iter.remainder(), [bad, good].concat());
+    }
+
+    #[test]
+    fn skips_idle_packets_only_when_asked() {
+        let mut idle = [0; 8];
+        crate::IdleGenerator::new().write(&mut idle).unwrap();
+        let data = packet(0x10, b"data");
+        let stream = [&idle, &data[..], &idle, &idle].concat();
+
+        let all = PacketIter::new(&stream)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(all.len(), 4);
+        assert!(all[0].is_idle() && !all[1].is_idle());
+
+        let mut iter = PacketIter::new(&stream).skip_idle();
+        assert_eq!(iter.next().unwrap().unwrap().as_bytes(), data.as_slice());
+        assert!(iter.next().is_none());
+        assert!(iter.remainder().is_empty());
+    }
+
+    #[test]
+    fn empty_buffers_hold_no_packets() {
+        let mut iter = PacketIter::new(&[]);
+        assert!(iter.next().is_none());
+        assert!(iter.remainder().is_empty());
+
+        // a packet's data field is at least one byte long
+        assert_eq!(
+            RawPacket::new(&[0x00, 0x10, 0xC0, 0x00, 0x00, 0x00]),
+            Err(Error::Truncated {
+                expected: 7,
+                actual: 6
+            })
+        );
+    }
+
+    #[test]
+    fn raw_packets_check_their_length() {
+        let bytes = packet(0x7F, b"data");
+        let packet = RawPacket::new(&bytes).unwrap();
+        assert_eq!(packet.primary_header().apid(), 0x7F);
+        assert_eq!(packet.data(), b"data");
+
+        assert_eq!(
+            RawPacket::new(&bytes[..9]),
+            Err(Error::LengthFieldMismatch {
+                expected: 9,
+                actual: 10
+            })
+        );
+    }
+}
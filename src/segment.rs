@@ -0,0 +1,548 @@
+//! Splitting user data too large for one space packet across several, using the sequence flags,
+//! and putting it back together on the receiving end.
+//!
+//! A [`Segmenter`] turns a block of user data into a first segment, continuation segments
+//! and a last segment, with consecutive sequence counts; a [`Reassembler`] (with the `std` feature)
+//! collects the segments for each APID and hands back the user data once the last one arrives.
+
+use crate::{Error, PrimaryHeader, SequenceFlags};
+
+#[cfg(feature = "std")]
+use crate::sequence::following;
+
+#[cfg(feature = "std")]
+use crate::RawPacket;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// One space packet's worth of segmented user data: its primary header, and the data it carries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment<'a> {
+    header: PrimaryHeader,
+    data: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    /// Returns the segment's primary header, with its sequence flags, sequence count and length filled in.
+    pub fn primary_header(&self) -> &PrimaryHeader {
+        &self.header
+    }
+
+    /// Returns the user data the segment carries.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the length of the segment's packet (primary header included), in bytes.
+    pub fn packet_len(&self) -> usize {
+        PrimaryHeader::LEN + self.data.len()
+    }
+
+    /// Writes the segment's packet to the start of `out`, returning the number of bytes written.
+    pub fn write_to(&self, out: &mut [u8]) -> Result<usize, Error> {
+        let len = self.packet_len();
+        if out.len() < len {
+            return Err(Error::CapacityExceeded {
+                capacity: out.len(),
+                needed: len,
+            });
+        }
+
+        out[..PrimaryHeader::LEN].copy_from_slice(self.header.as_bytes());
+        out[PrimaryHeader::LEN..len].copy_from_slice(self.data);
+        Ok(len)
+    }
+
+    /// Returns the segment's packet as a vector of bytes.
+    #[cfg(feature = "std")]
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.packet_len());
+        bytes.extend_from_slice(self.header.as_bytes());
+        bytes.extend_from_slice(self.data);
+        bytes
+    }
+}
+
+/// An iterator over the segments of a block of user data.
+///
+/// User data that fits in one segment goes out as a single unsegmented packet.
+#[derive(Clone, Debug)]
+pub struct Segmenter<'a> {
+    /// The header of the next segment, with its sequence count already set.
+    header: PrimaryHeader,
+    data: &'a [u8],
+    max_data_len: usize,
+    first: bool,
+}
+
+impl<'a> Segmenter<'a> {
+    /// Creates a segmenter splitting `data` into segments of up to `max_data_len` bytes each.
+    ///
+    /// Each segment's primary header is a copy of `header` (which gives the version number,
+    /// packet type, secondary header flag and APID), with the sequence flags and length set to suit.
+    /// The first segment keeps `header`'s sequence count, and each one after it gets the next count.
+    /// Empty user data makes no segments.
+    ///
+    /// Fails if `max_data_len` is 0 or more than a packet can hold.
+    pub fn new(header: PrimaryHeader, data: &'a [u8], max_data_len: usize) -> Result<Self, Error> {
+        if max_data_len == 0 {
+            return Err(Error::EmptySegment);
+        }
+        PrimaryHeader::default().set_packet_len(PrimaryHeader::LEN + max_data_len)?;
+
+        Ok(Self {
+            header,
+            data,
+            max_data_len,
+            first: true,
+        })
+    }
+
+    /// Returns the sequence count the next segment will get,
+    /// or the one following the last segment once they've all been produced.
+    pub fn next_sequence_count(&self) -> u16 {
+        self.header.sequence_count()
+    }
+}
+
+impl<'a> Iterator for Segmenter<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Segment<'a>> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let len = self.data.len().min(self.max_data_len);
+        let (data, rest) = self.data.split_at(len);
+        let flags = match (self.first, rest.is_empty()) {
+            (true, true) => SequenceFlags::Unsegmented,
+            (true, false) => SequenceFlags::First,
+            (false, false) => SequenceFlags::Continuation,
+            (false, true) => SequenceFlags::Last,
+        };
+
+        let mut header = self.header;
+        header.set_sequence_flags(flags);
+        // `len` is between 1 and `max_data_len`, so the length always fits
+        header.set_packet_len(PrimaryHeader::LEN + len).unwrap();
+
+        self.header.increment_sequence_count();
+        self.data = rest;
+        self.first = false;
+
+        Some(Segment { header, data })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let segments = self.data.len().div_ceil(self.max_data_len);
+        (segments, Some(segments))
+    }
+}
+
+impl ExactSizeIterator for Segmenter<'_> {}
+
+/// User data put back together from its segments (or taken from an unsegmented packet).
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reassembled {
+    /// The primary header of the first segment (or of the unsegmented packet).
+    pub header: PrimaryHeader,
+
+    /// The user data of all the segments, in order.
+    pub data: Vec<u8>,
+}
+
+/// User data whose segments are still arriving.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+struct Partial {
+    header: PrimaryHeader,
+    data: Vec<u8>,
+    next_count: u16,
+    started: Instant,
+}
+
+/// Puts segmented user data back together, separately for each APID.
+///
+/// Feed it every packet received with [`Self::push`]; segments are expected to arrive in order,
+/// with consecutive sequence counts. Segmented user data whose last segment hasn't arrived
+/// within the timeout is discarded by [`Self::expire`], which should be called now and then.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct Reassembler {
+    partials: HashMap<u16, Partial>,
+    timeout: Duration,
+    max_len: usize,
+}
+
+#[cfg(feature = "std")]
+impl Reassembler {
+    /// The default limit on the length of reassembled user data, in bytes.
+    pub const DEFAULT_MAX_LEN: usize = 1 << 20;
+
+    /// Creates a reassembler that gives up on segmented user data
+    /// whose last segment hasn't arrived `timeout` after its first.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            partials: HashMap::new(),
+            timeout,
+            max_len: Self::DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// Limits reassembled user data to `max_len` bytes; segmented user data growing longer than that is discarded.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Takes in `packet`, returning the user data it completes, if any.
+    ///
+    /// An unsegmented packet's data is returned straight away.
+    /// A segment that doesn't follow on from the previous one with its APID is an error,
+    /// and any user data that was being reassembled for that APID is discarded;
+    /// a first segment still starts reassembling new user data, though
+    /// (unless it's longer than the limit, in which case the missing segment is what's reported).
+    pub fn push(&mut self, packet: RawPacket) -> Result<Option<Reassembled>, Error> {
+        self.push_at(packet, Instant::now())
+    }
+
+    /// Takes in `packet`, received at `now`, returning the user data it completes, if any,
+    /// as in [`Self::push`].
+    pub fn push_at(
+        &mut self,
+        packet: RawPacket,
+        now: Instant,
+    ) -> Result<Option<Reassembled>, Error> {
+        let header = *packet.primary_header();
+        let apid = header.apid();
+        let count = header.sequence_count();
+        let data = packet.data();
+
+        match header.sequence_flags() {
+            SequenceFlags::Unsegmented => Ok(Some(Reassembled {
+                header,
+                data: data.to_vec(),
+            })),
+
+            SequenceFlags::First => {
+                let interrupted = self.partials.remove(&apid);
+                let checked = check_len(data.len(), self.max_len);
+                if checked.is_ok() {
+                    self.partials.insert(
+                        apid,
+                        Partial {
+                            header,
+                            data: data.to_vec(),
+                            next_count: following(count),
+                            started: now,
+                        },
+                    );
+                }
+
+                match interrupted {
+                    Some(partial) => Err(Error::SegmentMissing {
+                        apid,
+                        expected: partial.next_count,
+                        actual: count,
+                    }),
+                    None => checked.map(|()| None),
+                }
+            }
+
+            flags @ (SequenceFlags::Continuation | SequenceFlags::Last) => {
+                let partial = self
+                    .partials
+                    .get_mut(&apid)
+                    .ok_or(Error::UnexpectedSegment { apid, count })?;
+
+                if partial.next_count != count {
+                    let expected = partial.next_count;
+                    self.partials.remove(&apid);
+                    return Err(Error::SegmentMissing {
+                        apid,
+                        expected,
+                        actual: count,
+                    });
+                }
+
+                if let Err(err) = check_len(partial.data.len() + data.len(), self.max_len) {
+                    self.partials.remove(&apid);
+                    return Err(err);
+                }
+
+                partial.data.extend_from_slice(data);
+                partial.next_count = following(count);
+
+                if flags == SequenceFlags::Continuation {
+                    return Ok(None);
+                }
+
+                let partial = self.partials.remove(&apid).unwrap();
+                Ok(Some(Reassembled {
+                    header: partial.header,
+                    data: partial.data,
+                }))
+            }
+        }
+    }
+
+    /// Discards segmented user data that's timed out, returning the APIDs it was for.
+    pub fn expire(&mut self) -> Vec<u16> {
+        self.expire_at(Instant::now())
+    }
+
+    /// Discards segmented user data that's timed out as of `now`, returning the APIDs it was for.
+    pub fn expire_at(&mut self, now: Instant) -> Vec<u16> {
+        let timeout = self.timeout;
+        let mut expired = Vec::new();
+
+        self.partials.retain(|&apid, partial| {
+            let live = now.saturating_duration_since(partial.started) < timeout;
+            if !live {
+                expired.push(apid);
+            }
+            live
+        });
+
+        expired
+    }
+
+    /// Returns the number of APIDs with segmented user data still being reassembled.
+    pub fn in_progress(&self) -> usize {
+        self.partials.len()
+    }
+
+    /// Discards all user data being reassembled.
+    pub fn clear(&mut self) {
+        self.partials.clear();
+    }
+}
+
+/// Checks that reassembled user data `len` bytes long isn't longer than `max_len`.
+#[cfg(feature = "std")]
+fn check_len(len: usize, max_len: usize) -> Result<(), Error> {
+    if len > max_len {
+        return Err(Error::CapacityExceeded {
+            capacity: max_len,
+            needed: len,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(apid: u16, count: u16) -> PrimaryHeader {
+        let mut header = PrimaryHeader::default();
+        header.set_apid(apid).unwrap();
+        header.set_sequence_count(count).unwrap();
+        header
+    }
+
+    #[test]
+    fn segmenter_sets_flags_counts_and_lengths() {
+        let segments: Vec<_> = Segmenter::new(header(0x42, 16383), b"abcdefg", 3)
+            .unwrap()
+            .collect();
+
+        let flags: Vec<_> = segments
+            .iter()
+            .map(|segment| segment.primary_header().sequence_flags())
+            .collect();
+        assert_eq!(
+            flags,
+            [
+                SequenceFlags::First,
+                SequenceFlags::Continuation,
+                SequenceFlags::Last
+            ]
+        );
+        let counts: Vec<_> = segments
+            .iter()
+            .map(|segment| segment.primary_header().sequence_count())
+            .collect();
+        assert_eq!(counts, [16383, 0, 1]);
+        assert_eq!(segments[2].data(), b"g");
+        assert_eq!(segments[2].primary_header().packet_len(), 7);
+
+        let single: Vec<_> = Segmenter::new(header(0x42, 0), b"ab", 3).unwrap().collect();
+        assert_eq!(
+            single[0].primary_header().sequence_flags(),
+            SequenceFlags::Unsegmented
+        );
+    }
+
+    #[test]
+    fn segmenter_rejects_bad_segment_lengths() {
+        assert_eq!(
+            Segmenter::new(header(0x42, 0), b"ab", 0).err(),
+            Some(Error::EmptySegment)
+        );
+        assert!(Segmenter::new(header(0x42, 0), b"ab", 0x10000).is_ok());
+        assert!(Segmenter::new(header(0x42, 0), b"ab", 0x10001).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn reassembles_interleaved_apids() {
+        let a: Vec<_> = Segmenter::new(header(1, 10), b"hello, world", 5)
+            .unwrap()
+            .map(|segment| segment.to_vec())
+            .collect();
+        let b: Vec<_> = Segmenter::new(header(2, 500), b"goodbye", 4)
+            .unwrap()
+            .map(|segment| segment.to_vec())
+            .collect();
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+        let mut push = |bytes: &Vec<u8>| reassembler.push(RawPacket::new(bytes).unwrap()).unwrap();
+
+        assert_eq!(push(&a[0]), None);
+        assert_eq!(push(&b[0]), None);
+        assert_eq!(push(&a[1]), None);
+        let b_data = push(&b[1]).unwrap();
+        assert_eq!(b_data.data, b"goodbye");
+        assert_eq!(b_data.header.sequence_count(), 500);
+        assert_eq!(push(&a[2]).unwrap().data, b"hello, world");
+        assert_eq!(reassembler.in_progress(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn reports_missing_and_unexpected_segments() {
+        let segments: Vec<_> = Segmenter::new(header(3, 0), b"abcdefghi", 3)
+            .unwrap()
+            .map(|segment| segment.to_vec())
+            .collect();
+        let packet = |i: usize| RawPacket::new(&segments[i]).unwrap();
+        let mut reassembler = Reassembler::new(Duration::from_secs(1));
+
+        assert_eq!(
+            reassembler.push(packet(1)),
+            Err(Error::UnexpectedSegment { apid: 3, count: 1 })
+        );
+
+        // a first segment interrupting another is reported, but starts afresh
+        reassembler.push(packet(0)).unwrap();
+        assert_eq!(
+            reassembler.push(packet(0)),
+            Err(Error::SegmentMissing {
+                apid: 3,
+                expected: 1,
+                actual: 0
+            })
+        );
+        reassembler.push(packet(1)).unwrap();
+        assert_eq!(
+            reassembler.push(packet(2)).unwrap().unwrap().data,
+            b"abcdefghi"
+        );
+
+        reassembler.push(packet(0)).unwrap();
+        assert_eq!(
+            reassembler.push(packet(2)),
+            Err(Error::SegmentMissing {
+                apid: 3,
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert_eq!(reassembler.in_progress(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn limits_reassembled_length() {
+        let segments: Vec<_> = Segmenter::new(header(4, 0), b"abcdefghi", 3)
+            .unwrap()
+            .map(|segment| segment.to_vec())
+            .collect();
+        let packet = |i: usize| RawPacket::new(&segments[i]).unwrap();
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1)).with_max_len(5);
+        reassembler.push(packet(0)).unwrap();
+        assert_eq!(
+            reassembler.push(packet(1)),
+            Err(Error::CapacityExceeded {
+                capacity: 5,
+                needed: 6
+            })
+        );
+        assert_eq!(reassembler.in_progress(), 0);
+
+        // a first segment too long to start with doesn't hide the segment it interrupted
+        let mut reassembler = Reassembler::new(Duration::from_secs(1)).with_max_len(3);
+        reassembler.push(packet(0)).unwrap();
+        reassembler = reassembler.with_max_len(2);
+        assert_eq!(
+            reassembler.push(packet(0)),
+            Err(Error::SegmentMissing {
+                apid: 4,
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(reassembler.in_progress(), 0);
+        assert_eq!(
+            reassembler.push(packet(0)),
+            Err(Error::CapacityExceeded {
+                capacity: 2,
+                needed: 3
+            })
+        );
+        assert_eq!(reassembler.in_progress(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn expires_partial_data() {
+        let a: Vec<_> = Segmenter::new(header(5, 0), b"abcdef", 3)
+            .unwrap()
+            .map(|segment| segment.to_vec())
+            .collect();
+        let b: Vec<_> = Segmenter::new(header(6, 0), b"abcdef", 3)
+            .unwrap()
+            .map(|segment| segment.to_vec())
+            .collect();
+
+        let start = Instant::now();
+        let mut reassembler = Reassembler::new(Duration::from_secs(10));
+        reassembler
+            .push_at(RawPacket::new(&a[0]).unwrap(), start)
+            .unwrap();
+        reassembler
+            .push_at(
+                RawPacket::new(&b[0]).unwrap(),
+                start + Duration::from_secs(5),
+            )
+            .unwrap();
+
+        assert!(reassembler
+            .expire_at(start + Duration::from_secs(9))
+            .is_empty());
+        assert_eq!(reassembler.expire_at(start + Duration::from_secs(10)), [5]);
+        assert_eq!(reassembler.in_progress(), 1);
+
+        assert_eq!(
+            reassembler.push_at(
+                RawPacket::new(&a[1]).unwrap(),
+                start + Duration::from_secs(11)
+            ),
+            Err(Error::UnexpectedSegment { apid: 5, count: 1 })
+        );
+        let data = reassembler
+            .push_at(
+                RawPacket::new(&b[1]).unwrap(),
+                start + Duration::from_secs(11),
+            )
+            .unwrap();
+        assert_eq!(data.unwrap().data, b"abcdef");
+    }
+}
@@ -0,0 +1,613 @@
+//! Per-APID packet sequence counts: counters for stamping outgoing packets,
+//! and trackers for checking incoming ones.
+//!
+//! CCSDS counts packets separately for each APID, so a sequence count belongs to the APID,
+//! not to any one packet struct. A [`SequenceCounters`] registry hands out the next 14-bit count
+//! for an APID and writes it into a packet's primary header:
+//! `counters.stamp(tlm.primary_header_mut())`.
+//! On the receiving end, a [`SequenceTracker`] (or an [`ApidTracker`] for a single APID)
+//! notices dropped, repeated and reordered packets: `tracker.track(tlm.primary_header())`.
+
+#[cfg(target_has_atomic = "16")]
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use crate::{Error, PrimaryHeader};
+
+/// The number of distinct APIDs.
+#[cfg(target_has_atomic = "16")]
+const APID_COUNT: usize = PrimaryHeader::MAX_APID as usize + 1;
+
+/// A registry of per-APID sequence counters.
+pub trait SequenceCounters {
+    /// Returns the sequence count for the next packet with the APID `apid`, and advances its counter,
+    /// wrapping around to zero after [`PrimaryHeader::MAX_SEQUENCE_COUNT`].
+    ///
+    /// An APID's first packet gets a count of zero, unless it's been set otherwise.
+    fn next_count(&mut self, apid: u16) -> Result<u16, Error>;
+
+    /// Sets the sequence count of the packet whose primary header is `header`
+    /// to the next count for its APID, returning that count.
+    fn stamp(&mut self, header: &mut PrimaryHeader) -> Result<u16, Error> {
+        let count = self.next_count(header.apid())?;
+        header.set_sequence_count(count)?;
+        Ok(count)
+    }
+}
+
+fn check_apid(apid: u16) -> Result<(), Error> {
+    if apid > PrimaryHeader::MAX_APID {
+        return Err(Error::InvalidApid(apid));
+    }
+
+    Ok(())
+}
+
+fn check_count(count: u16) -> Result<(), Error> {
+    if count > PrimaryHeader::MAX_SEQUENCE_COUNT {
+        return Err(Error::InvalidSequenceCount(count));
+    }
+
+    Ok(())
+}
+
+/// Returns the count following `count`.
+pub(crate) fn following(count: u16) -> u16 {
+    count.wrapping_add(1) & PrimaryHeader::MAX_SEQUENCE_COUNT
+}
+
+/// A [`SequenceCounters`] registry with room for up to `N` APIDs, which doesn't allocate.
+#[derive(Clone, Debug)]
+pub struct SequenceCounterArray<const N: usize> {
+    /// (APID, next count) pairs for the APIDs seen so far, in order of first use.
+    counters: [(u16, u16); N],
+    len: usize,
+}
+
+impl<const N: usize> SequenceCounterArray<N> {
+    /// Creates a registry with no APIDs.
+    pub const fn new() -> Self {
+        Self {
+            counters: [(0, 0); N],
+            len: 0,
+        }
+    }
+
+    /// Returns the number of APIDs the registry has counters for.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the registry has no counters yet.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sets the count the next packet with the APID `apid` will get.
+    pub fn set_next_count(&mut self, apid: u16, count: u16) -> Result<(), Error> {
+        check_count(count)?;
+        *self.counter(apid)? = count;
+        Ok(())
+    }
+
+    /// Forgets all the counters.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the counter (holding the next count) for `apid`, adding one if there isn't one yet.
+    fn counter(&mut self, apid: u16) -> Result<&mut u16, Error> {
+        check_apid(apid)?;
+
+        let index = match self.counters[..self.len]
+            .iter()
+            .position(|&(a, _)| a == apid)
+        {
+            Some(index) => index,
+            None if self.len < N => {
+                self.counters[self.len] = (apid, 0);
+                self.len += 1;
+                self.len - 1
+            }
+            None => return Err(Error::TooManyApids { capacity: N }),
+        };
+
+        Ok(&mut self.counters[index].1)
+    }
+}
+
+impl<const N: usize> Default for SequenceCounterArray<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SequenceCounters for SequenceCounterArray<N> {
+    fn next_count(&mut self, apid: u16) -> Result<u16, Error> {
+        let counter = self.counter(apid)?;
+        let count = *counter;
+        *counter = following(count);
+        Ok(count)
+    }
+}
+
+/// A [`SequenceCounters`] registry backed by a `HashMap`, with room for any number of APIDs.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct SequenceCounterMap {
+    counters: std::collections::HashMap<u16, u16>,
+}
+
+#[cfg(feature = "std")]
+impl SequenceCounterMap {
+    /// Creates a registry with no APIDs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the count the next packet with the APID `apid` will get.
+    pub fn set_next_count(&mut self, apid: u16, count: u16) -> Result<(), Error> {
+        check_apid(apid)?;
+        check_count(count)?;
+        self.counters.insert(apid, count);
+        Ok(())
+    }
+
+    /// Forgets all the counters.
+    pub fn clear(&mut self) {
+        self.counters.clear();
+    }
+}
+
+#[cfg(feature = "std")]
+impl SequenceCounters for SequenceCounterMap {
+    fn next_count(&mut self, apid: u16) -> Result<u16, Error> {
+        check_apid(apid)?;
+
+        let counter = self.counters.entry(apid).or_insert(0);
+        let count = *counter;
+        *counter = following(count);
+        Ok(count)
+    }
+}
+
+/// A [`SequenceCounters`] registry with an atomic counter for every APID,
+/// which can be shared between threads (or interrupt handlers) and used through a shared reference.
+#[cfg(target_has_atomic = "16")]
+#[derive(Debug)]
+pub struct AtomicSequenceCounters {
+    counters: [AtomicU16; APID_COUNT],
+}
+
+#[cfg(target_has_atomic = "16")]
+impl AtomicSequenceCounters {
+    /// Creates a registry with every APID's counter at zero.
+    pub const fn new() -> Self {
+        Self {
+            counters: [const { AtomicU16::new(0) }; APID_COUNT],
+        }
+    }
+
+    /// Returns the sequence count for the next packet with the APID `apid`, and advances its counter,
+    /// wrapping around to zero after [`PrimaryHeader::MAX_SEQUENCE_COUNT`].
+    pub fn next_count(&self, apid: u16) -> Result<u16, Error> {
+        check_apid(apid)?;
+
+        // 2^16 is a multiple of 2^14, so the u16 wrapping around keeps the 14-bit count in step
+        let count = self.counters[apid as usize].fetch_add(1, Ordering::Relaxed);
+        Ok(count & PrimaryHeader::MAX_SEQUENCE_COUNT)
+    }
+
+    /// Sets the sequence count of the packet whose primary header is `header`
+    /// to the next count for its APID, returning that count.
+    pub fn stamp(&self, header: &mut PrimaryHeader) -> Result<u16, Error> {
+        let count = self.next_count(header.apid())?;
+        header.set_sequence_count(count)?;
+        Ok(count)
+    }
+
+    /// Sets the count the next packet with the APID `apid` will get.
+    pub fn set_next_count(&self, apid: u16, count: u16) -> Result<(), Error> {
+        check_apid(apid)?;
+        check_count(count)?;
+        self.counters[apid as usize].store(count, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(target_has_atomic = "16")]
+impl Default for AtomicSequenceCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_has_atomic = "16")]
+impl SequenceCounters for AtomicSequenceCounters {
+    fn next_count(&mut self, apid: u16) -> Result<u16, Error> {
+        AtomicSequenceCounters::next_count(self, apid)
+    }
+}
+
+#[cfg(target_has_atomic = "16")]
+impl SequenceCounters for &AtomicSequenceCounters {
+    fn next_count(&mut self, apid: u16) -> Result<u16, Error> {
+        AtomicSequenceCounters::next_count(self, apid)
+    }
+}
+
+/// The number of most recent sequence counts an [`ApidTracker`] remembers receiving,
+/// for telling late packets from duplicates.
+const RECENT_COUNTS: u16 = 64;
+
+/// The number of distinct sequence counts.
+const COUNT_RANGE: u16 = PrimaryHeader::MAX_SEQUENCE_COUNT + 1;
+
+/// What a received packet's sequence count says about the packets before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SequenceEvent {
+    /// The first packet seen with its APID.
+    First,
+
+    /// The packet follows on from the latest one.
+    InOrder,
+
+    /// The packet skips ahead of the latest one.
+    Gap {
+        /// The number of sequence counts skipped.
+        missing: u16,
+    },
+
+    /// The packet has the same count as one received recently.
+    Duplicate,
+
+    /// The packet has the count of one that was missing: it arrived late.
+    /// If it's more than 64 counts behind, it may also be a duplicate of a packet
+    /// too old to be remembered.
+    OutOfOrder {
+        /// The number of sequence counts it's behind the latest packet.
+        behind: u16,
+    },
+}
+
+/// Cumulative sequence count statistics for one APID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SequenceStats {
+    /// The number of packets received.
+    pub received: u64,
+
+    /// The number of gaps seen.
+    pub gaps: u64,
+
+    /// The number of packets skipped over by gaps that haven't since arrived late.
+    ///
+    /// Packets arriving more than 64 counts late can't be told from duplicates of old packets,
+    /// so they're still counted as missing.
+    pub missing: u64,
+
+    /// The number of duplicate packets received.
+    pub duplicates: u64,
+
+    /// The number of packets that arrived late.
+    pub out_of_order: u64,
+}
+
+/// Tracks the sequence counts of the packets received with one APID.
+///
+/// A count up to half the 14-bit range (8192) ahead of the latest packet's counts as a gap;
+/// any other count is taken to be behind it, either as a duplicate or a late arrival.
+#[derive(Clone, Debug, Default)]
+pub struct ApidTracker {
+    /// The latest packet's count.
+    latest: Option<u16>,
+
+    /// Bit `i` is set if the packet `i` counts behind the latest has been received.
+    recent: u64,
+
+    /// Bit `i` is set if the packet `i` counts behind the latest was skipped by a gap
+    /// and hasn't arrived since.
+    skipped: u64,
+
+    stats: SequenceStats,
+}
+
+impl ApidTracker {
+    /// Creates a tracker that hasn't seen any packets.
+    pub const fn new() -> Self {
+        Self {
+            latest: None,
+            recent: 0,
+            skipped: 0,
+            stats: SequenceStats {
+                received: 0,
+                gaps: 0,
+                missing: 0,
+                duplicates: 0,
+                out_of_order: 0,
+            },
+        }
+    }
+
+    /// Records a packet with the sequence count `count` (of which only the low 14 bits are used),
+    /// and says how it fits with the packets before it.
+    pub fn track(&mut self, count: u16) -> SequenceEvent {
+        let count = count & PrimaryHeader::MAX_SEQUENCE_COUNT;
+        self.stats.received += 1;
+
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => {
+                self.latest = Some(count);
+                self.recent = 1;
+                self.skipped = 0;
+                return SequenceEvent::First;
+            }
+        };
+
+        let ahead = count.wrapping_sub(latest) & PrimaryHeader::MAX_SEQUENCE_COUNT;
+        if ahead == 0 {
+            self.stats.duplicates += 1;
+            return SequenceEvent::Duplicate;
+        }
+
+        if ahead <= COUNT_RANGE / 2 {
+            self.latest = Some(count);
+            self.recent = self.recent.checked_shl(ahead as u32).unwrap_or(0) | 1;
+            self.skipped = self.skipped.checked_shl(ahead as u32).unwrap_or(0);
+
+            if ahead == 1 {
+                return SequenceEvent::InOrder;
+            }
+
+            // the counts skipped are 1 to ahead - 1 behind the new latest
+            self.skipped |= u64::MAX
+                .checked_shl(ahead as u32)
+                .map_or(u64::MAX, |mask| !mask)
+                & !1;
+            self.stats.gaps += 1;
+            self.stats.missing += ahead as u64 - 1;
+            return SequenceEvent::Gap { missing: ahead - 1 };
+        }
+
+        let behind = COUNT_RANGE - ahead;
+        if behind < RECENT_COUNTS {
+            let bit = 1 << behind;
+            if self.recent & bit != 0 {
+                self.stats.duplicates += 1;
+                return SequenceEvent::Duplicate;
+            }
+
+            self.recent |= bit;
+            if self.skipped & bit != 0 {
+                self.skipped &= !bit;
+                self.stats.missing -= 1;
+            }
+        }
+
+        self.stats.out_of_order += 1;
+        SequenceEvent::OutOfOrder { behind }
+    }
+
+    /// Returns the latest packet's sequence count, if any packets have been seen.
+    pub const fn latest(&self) -> Option<u16> {
+        self.latest
+    }
+
+    /// Returns the statistics so far.
+    pub const fn stats(&self) -> &SequenceStats {
+        &self.stats
+    }
+}
+
+/// Tracks the sequence counts of received packets, for every APID,
+/// reporting gaps, duplicates and out-of-order packets.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct SequenceTracker {
+    apids: std::collections::HashMap<u16, ApidTracker>,
+}
+
+#[cfg(feature = "std")]
+impl SequenceTracker {
+    /// Creates a tracker that hasn't seen any packets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a packet with the primary header `header`,
+    /// and says how it fits with the packets before it with the same APID.
+    pub fn track(&mut self, header: &PrimaryHeader) -> SequenceEvent {
+        self.apids
+            .entry(header.apid())
+            .or_default()
+            .track(header.sequence_count())
+    }
+
+    /// Returns the statistics so far for `apid`, if any packets with that APID have been seen.
+    pub fn stats(&self, apid: u16) -> Option<&SequenceStats> {
+        self.apids.get(&apid).map(ApidTracker::stats)
+    }
+
+    /// Returns the tracker for each APID seen so far, in no particular order.
+    pub fn apids(&self) -> impl Iterator<Item = (u16, &ApidTracker)> {
+        self.apids.iter().map(|(&apid, tracker)| (apid, tracker))
+    }
+
+    /// Forgets everything seen so far for `apid`.
+    pub fn reset(&mut self, apid: u16) {
+        self.apids.remove(&apid);
+    }
+
+    /// Forgets everything seen so far.
+    pub fn clear(&mut self) {
+        self.apids.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay(counts: &[u16]) -> (ApidTracker, Vec<SequenceEvent>) {
+        let mut tracker = ApidTracker::new();
+        let events = counts.iter().map(|&count| tracker.track(count)).collect();
+        (tracker, events)
+    }
+
+    #[test]
+    fn array_counters_wrap_around_per_apid() {
+        let mut counters = SequenceCounterArray::<2>::new();
+        counters.set_next_count(0x10, 0x3FFF).unwrap();
+
+        assert_eq!(counters.next_count(0x10), Ok(0x3FFF));
+        assert_eq!(counters.next_count(0x11), Ok(0));
+        assert_eq!(counters.next_count(0x10), Ok(0));
+        assert_eq!(counters.next_count(0x11), Ok(1));
+        assert_eq!(counters.len(), 2);
+
+        assert_eq!(
+            counters.next_count(0x12),
+            Err(Error::TooManyApids { capacity: 2 })
+        );
+        assert_eq!(
+            counters.set_next_count(0x10, 0x4000),
+            Err(Error::InvalidSequenceCount(0x4000))
+        );
+
+        counters.clear();
+        assert!(counters.is_empty());
+        assert_eq!(counters.next_count(0x12), Ok(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn map_counters_wrap_around_per_apid() {
+        let mut counters = SequenceCounterMap::new();
+        counters.set_next_count(0x7FF, 0x3FFF).unwrap();
+
+        assert_eq!(counters.next_count(0x7FF), Ok(0x3FFF));
+        assert_eq!(counters.next_count(0x7FF), Ok(0));
+        assert_eq!(counters.next_count(0), Ok(0));
+        assert_eq!(counters.next_count(0x800), Err(Error::InvalidApid(0x800)));
+    }
+
+    #[cfg(target_has_atomic = "16")]
+    #[test]
+    fn atomic_counters_wrap_around_per_apid() {
+        let counters = AtomicSequenceCounters::new();
+        counters.set_next_count(0x20, 0x3FFF).unwrap();
+
+        assert_eq!(counters.next_count(0x20), Ok(0x3FFF));
+        assert_eq!(counters.next_count(0x20), Ok(0));
+        assert_eq!(counters.next_count(0x21), Ok(0));
+        // every APID has a counter, so the only APIDs turned away are out of range ones
+        assert_eq!(counters.next_count(0x800), Err(Error::InvalidApid(0x800)));
+
+        let handed_out = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..100)
+                            .map(|_| counters.next_count(0x22).unwrap())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            let mut counts: Vec<_> = threads
+                .into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect();
+            counts.sort_unstable();
+            counts
+        });
+        assert_eq!(handed_out, (0..400).collect::<Vec<_>>());
+
+        let mut header = PrimaryHeader::default();
+        header.set_apid(0x21).unwrap();
+        assert_eq!(counters.stamp(&mut header), Ok(1));
+        assert_eq!(SequenceCounters::stamp(&mut &counters, &mut header), Ok(2));
+        assert_eq!(header.sequence_count(), 2);
+    }
+
+    #[test]
+    fn stamping_sets_the_sequence_count() {
+        let mut header = PrimaryHeader::default();
+        header.set_apid(0x42).unwrap();
+
+        let mut counters = SequenceCounterArray::<1>::new();
+        counters.set_next_count(0x42, 7).unwrap();
+        assert_eq!(counters.stamp(&mut header), Ok(7));
+        assert_eq!(header.sequence_count(), 7);
+        assert_eq!(header.apid(), 0x42);
+        assert_eq!(counters.stamp(&mut header), Ok(8));
+        assert_eq!(header.sequence_count(), 8);
+    }
+
+    #[test]
+    fn late_packets_only_count_against_gaps() {
+        let (tracker, events) = replay(&[0, 1, 2, 5, 3, 3, 16383]);
+
+        assert_eq!(
+            events,
+            [
+                SequenceEvent::First,
+                SequenceEvent::InOrder,
+                SequenceEvent::InOrder,
+                SequenceEvent::Gap { missing: 2 },
+                SequenceEvent::OutOfOrder { behind: 2 },
+                SequenceEvent::Duplicate,
+                SequenceEvent::OutOfOrder { behind: 6 },
+            ]
+        );
+        // count 4 never arrived; 16383 was never skipped over
+        let stats = tracker.stats();
+        assert_eq!(stats.gaps, 1);
+        assert_eq!(stats.missing, 1);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.out_of_order, 2);
+        assert_eq!(stats.received, 7);
+    }
+
+    #[test]
+    fn counts_wrap_around() {
+        let (tracker, events) = replay(&[16382, 16383, 0, 1]);
+        assert_eq!(events[1..], [SequenceEvent::InOrder; 3]);
+        assert_eq!(tracker.latest(), Some(1));
+
+        let (tracker, events) = replay(&[16382, 1, 16383, 0, 0]);
+        assert_eq!(
+            events,
+            [
+                SequenceEvent::First,
+                SequenceEvent::Gap { missing: 2 },
+                SequenceEvent::OutOfOrder { behind: 2 },
+                SequenceEvent::OutOfOrder { behind: 1 },
+                SequenceEvent::Duplicate,
+            ]
+        );
+        assert_eq!(tracker.stats().missing, 0);
+        assert_eq!(tracker.latest(), Some(1));
+    }
+
+    #[test]
+    fn gaps_longer_than_memory() {
+        let (tracker, events) = replay(&[10, 110, 109, 40, 20]);
+        assert_eq!(
+            events,
+            [
+                SequenceEvent::First,
+                SequenceEvent::Gap { missing: 99 },
+                SequenceEvent::OutOfOrder { behind: 1 },
+                SequenceEvent::OutOfOrder { behind: 70 },
+                SequenceEvent::OutOfOrder { behind: 90 },
+            ]
+        );
+        // only the late packet recent enough to remember is taken off the missing count
+        assert_eq!(tracker.stats().missing, 98);
+
+        // half the range ahead is a gap; any further is behind
+        let (_, events) = replay(&[0, 8192]);
+        assert_eq!(events[1], SequenceEvent::Gap { missing: 8191 });
+        let (_, events) = replay(&[0, 8193]);
+        assert_eq!(events[1], SequenceEvent::OutOfOrder { behind: 8191 });
+    }
+}
@@ -0,0 +1,231 @@
+//! Telemetry timestamps: the layouts of the cFS telemetry secondary header for each of
+//! cFE's packet time formats (`CFE_MISSION_SB_PACKET_TIME_FORMAT`).
+//!
+//! Telemetry packet types are generic over a [`TimeFormat`];
+//! [`Telemetry`](crate::Telemetry) and friends use [`Cfe32_16`], cFE's default.
+//! The CCSDS time codes [`Cuc`](crate::Cuc) and [`Cds`](crate::Cds) can be used as time formats too.
+
+use core::time::Duration;
+
+use crate::{AnyBitPattern, NoPadding};
+
+/// The layout of the cFS telemetry secondary header for one of cFE's packet time formats.
+pub trait TimeFormat: Copy + core::fmt::Debug + 'static {
+    /// The bytes of the telemetry secondary header: the timestamp, plus any structure padding.
+    type Header: AnyBitPattern + NoPadding + AsRef<[u8]> + AsMut<[u8]> + Default + core::fmt::Debug;
+
+    /// A decoded timestamp.
+    type Timestamp: Copy;
+
+    /// Decodes the timestamp at the start of `header`, the bytes of a telemetry secondary header.
+    fn timestamp(header: &[u8]) -> Self::Timestamp;
+
+    /// Encodes `timestamp` at the start of `header`, the bytes of a telemetry secondary header.
+    fn set_timestamp(header: &mut [u8], timestamp: Self::Timestamp);
+
+    /// Returns the timestamp for `seconds` seconds + `nanoseconds` nanoseconds
+    /// since the flight-software epoch, rounded down to the format's resolution.
+    fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> Self::Timestamp;
+
+    /// Returns the time since the flight-software epoch that `timestamp` stands for,
+    /// rounded down to the nanosecond.
+    fn to_duration(timestamp: Self::Timestamp) -> Duration;
+}
+
+/// Returns the length of the headers (structure padding included) of a cFS telemetry message
+/// using the time format `F`, in bytes, excluding any extended header.
+pub(crate) const fn telemetry_header_len<F: TimeFormat>() -> usize {
+    crate::PrimaryHeader::LEN + core::mem::size_of::<F::Header>()
+}
+
+/// cFE's default packet time format (`32_16`): 32 bits of seconds,
+/// then 16 bits of subseconds in units of 2<sup>&minus;16</sup> s, then 4 bytes of structure padding.
+///
+/// Timestamps are (seconds since flight-software epoch, subseconds) tuples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cfe32_16;
+
+/// The `32_16` packet time format, without the structure padding that follows the timestamp.
+///
+/// Timestamps are (seconds since flight-software epoch, subseconds in units of 2<sup>&minus;16</sup> s) tuples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cfe32_16Packed;
+
+/// cFE's `32_32` packet time format: 32 bits of seconds,
+/// then 32 bits of subseconds in units of 2<sup>&minus;32</sup> s.
+///
+/// Timestamps are (seconds since flight-software epoch, subseconds) tuples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cfe32_32;
+
+/// cFE's `32_M_20` packet time format: 32 bits of seconds,
+/// then 20 bits of microseconds followed by 12 reserved bits.
+///
+/// Timestamps are (seconds since flight-software epoch, microseconds) tuples.
+/// Setting a timestamp with a whole second or more of microseconds carries the whole seconds
+/// into the seconds field (wrapping around past `u32::MAX`), so the microseconds field is always below 1,000,000.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cfe32Micro;
+
+impl TimeFormat for Cfe32_16 {
+    type Header = [u8; 10];
+    type Timestamp = (u32, u16);
+
+    fn timestamp(header: &[u8]) -> (u32, u16) {
+        Cfe32_16Packed::timestamp(header)
+    }
+
+    fn set_timestamp(header: &mut [u8], timestamp: (u32, u16)) {
+        Cfe32_16Packed::set_timestamp(header, timestamp);
+    }
+
+    fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> (u32, u16) {
+        Cfe32_16Packed::from_secs_nanos(seconds, nanoseconds)
+    }
+
+    fn to_duration(timestamp: (u32, u16)) -> Duration {
+        Cfe32_16Packed::to_duration(timestamp)
+    }
+}
+
+impl TimeFormat for Cfe32_16Packed {
+    type Header = [u8; 6];
+    type Timestamp = (u32, u16);
+
+    fn timestamp(header: &[u8]) -> (u32, u16) {
+        let seconds = u32::from_be_bytes(header[0..4].try_into().unwrap());
+        let subsecs = u16::from_be_bytes(header[4..6].try_into().unwrap());
+
+        (seconds, subsecs)
+    }
+
+    fn set_timestamp(header: &mut [u8], (seconds, subsecs): (u32, u16)) {
+        header[0..4].copy_from_slice(&seconds.to_be_bytes());
+        header[4..6].copy_from_slice(&subsecs.to_be_bytes());
+    }
+
+    fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> (u32, u16) {
+        let (seconds, nanoseconds) = normalize(seconds, nanoseconds);
+        // subseconds, in units of 2^-16 sec
+        let subsecs = (nanoseconds as u64 * (1 << 16)) / 1_000_000_000;
+
+        (seconds as u32, subsecs as u16)
+    }
+
+    fn to_duration((seconds, subsecs): (u32, u16)) -> Duration {
+        let nanos = (subsecs as u64 * 1_000_000_000) >> 16;
+        Duration::new(seconds as u64, nanos as u32)
+    }
+}
+
+impl TimeFormat for Cfe32_32 {
+    type Header = [u8; 8];
+    type Timestamp = (u32, u32);
+
+    fn timestamp(header: &[u8]) -> (u32, u32) {
+        let seconds = u32::from_be_bytes(header[0..4].try_into().unwrap());
+        let subsecs = u32::from_be_bytes(header[4..8].try_into().unwrap());
+
+        (seconds, subsecs)
+    }
+
+    fn set_timestamp(header: &mut [u8], (seconds, subsecs): (u32, u32)) {
+        header[0..4].copy_from_slice(&seconds.to_be_bytes());
+        header[4..8].copy_from_slice(&subsecs.to_be_bytes());
+    }
+
+    fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> (u32, u32) {
+        let (seconds, nanoseconds) = normalize(seconds, nanoseconds);
+        // subseconds, in units of 2^-32 sec
+        let subsecs = (nanoseconds as u64 * (1 << 32)) / 1_000_000_000;
+
+        (seconds as u32, subsecs as u32)
+    }
+
+    fn to_duration((seconds, subsecs): (u32, u32)) -> Duration {
+        let nanos = (subsecs as u64 * 1_000_000_000) >> 32;
+        Duration::new(seconds as u64, nanos as u32)
+    }
+}
+
+impl TimeFormat for Cfe32Micro {
+    type Header = [u8; 8];
+    type Timestamp = (u32, u32);
+
+    fn timestamp(header: &[u8]) -> (u32, u32) {
+        let seconds = u32::from_be_bytes(header[0..4].try_into().unwrap());
+        let micros = u32::from_be_bytes(header[4..8].try_into().unwrap()) >> 12;
+
+        (seconds, micros)
+    }
+
+    fn set_timestamp(header: &mut [u8], (seconds, micros): (u32, u32)) {
+        // the field only has room for 20 bits, so carry whole seconds out of it
+        let seconds = seconds.wrapping_add(micros / 1_000_000);
+        let micros = micros % 1_000_000;
+
+        header[0..4].copy_from_slice(&seconds.to_be_bytes());
+        header[4..8].copy_from_slice(&(micros << 12).to_be_bytes());
+    }
+
+    fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> (u32, u32) {
+        let (seconds, nanoseconds) = normalize(seconds, nanoseconds);
+        (seconds as u32, nanoseconds / 1_000)
+    }
+
+    fn to_duration((seconds, micros): (u32, u32)) -> Duration {
+        Duration::new(seconds as u64, 0) + Duration::from_micros(micros as u64)
+    }
+}
+
+/// Carries whole seconds out of `nanoseconds`, leaving it under a second.
+pub(crate) fn normalize(seconds: u64, nanoseconds: u32) -> (u64, u32) {
+    (
+        seconds.wrapping_add((nanoseconds / 1_000_000_000) as u64),
+        nanoseconds % 1_000_000_000,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn micro_timestamps_carry_whole_seconds() {
+        let mut header = [0; 8];
+
+        Cfe32Micro::set_timestamp(&mut header, (5, 999_999));
+        assert_eq!(header, [0, 0, 0, 5, 0xF4, 0x23, 0xF0, 0x00]);
+        assert_eq!(Cfe32Micro::timestamp(&header), (5, 999_999));
+
+        Cfe32Micro::set_timestamp(&mut header, (5, 2_500_000));
+        assert_eq!(Cfe32Micro::timestamp(&header), (7, 500_000));
+
+        // 2^20 microseconds wouldn't fit in the field as it is
+        Cfe32Micro::set_timestamp(&mut header, (5, 1 << 20));
+        assert_eq!(Cfe32Micro::timestamp(&header), (6, 48_576));
+
+        Cfe32Micro::set_timestamp(&mut header, (u32::MAX, 1_000_001));
+        assert_eq!(Cfe32Micro::timestamp(&header), (0, 1));
+    }
+
+    #[test]
+    fn timestamps_carry_whole_seconds_out_of_nanoseconds() {
+        assert_eq!(
+            Cfe32_16Packed::from_secs_nanos(10, 2_500_000_000),
+            (12, 0x8000)
+        );
+        assert_eq!(
+            Cfe32_32::from_secs_nanos(10, 2_500_000_000),
+            (12, 0x8000_0000)
+        );
+        assert_eq!(
+            Cfe32Micro::from_secs_nanos(10, 2_500_000_000),
+            (12, 500_000)
+        );
+        assert_eq!(
+            Cfe32_32::from_secs_nanos(u32::MAX as u64, 1_000_000_000),
+            (0, 0)
+        );
+    }
+}
@@ -0,0 +1,712 @@
+//! CCSDS time codes (CCSDS 301.0-B): the CCSDS Unsegmented Code ([`Cuc`])
+//! and the CCSDS Day Segmented code ([`Cds`]).
+//!
+//! Both can be encoded and decoded with or without their P-field,
+//! compared, offset by a [`Duration`], and converted to and from the [`Duration`] since their epoch.
+//! Each also implements [`TimeFormat`], with no structure padding,
+//! so it can be used as the timestamp of a telemetry packet:
+//! `TelemetryPacket<T, MsgIdV1, Cuc<4, 2>>` is laid out just like cFE's `32_16` format without the padding.
+
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::time::Duration;
+
+use crate::time::normalize;
+use crate::{Error, TimeFormat};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+const MILLIS_PER_DAY: u32 = 86_400_000;
+
+/// Which epoch a time code counts from, as given by its P-field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TimeCodeEpoch {
+    /// The CCSDS epoch, 1958-01-01T00:00:00 TAI (a "level 1" time code).
+    #[default]
+    Ccsds,
+
+    /// An epoch defined by the agency or mission (a "level 2" time code).
+    Agency,
+}
+
+/// A CCSDS Unsegmented Code time: a count of seconds (`COARSE` octets of it)
+/// and binary fractions of a second (`FINE` octets of it) since some epoch.
+///
+/// `COARSE` may be 1 to 7 and `FINE` 0 to 6; other values fail to compile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cuc<const COARSE: usize, const FINE: usize> {
+    coarse: u64,
+    fine: u64,
+}
+
+impl<const COARSE: usize, const FINE: usize> Cuc<COARSE, FINE> {
+    const CHECK: () = assert!(
+        COARSE >= 1 && COARSE <= 7 && FINE <= 6,
+        "CUC time codes have 1 to 7 octets of coarse time and 0 to 6 octets of fine time"
+    );
+
+    /// The length of the time code, excluding the P-field, in bytes.
+    pub const LEN: usize = COARSE + FINE;
+
+    /// The length of the time code's P-field, in bytes.
+    pub const P_FIELD_LEN: usize = if COARSE > 4 || FINE > 3 { 2 } else { 1 };
+
+    /// The largest possible coarse time, in seconds.
+    pub const MAX_COARSE: u64 = (1 << (8 * COARSE)) - 1;
+
+    /// The largest possible fine time, in units of 2<sup>&minus;8&times;`FINE`</sup> seconds.
+    pub const MAX_FINE: u64 = (1 << (8 * FINE)) - 1;
+
+    /// Creates a time code from its coarse time (in seconds)
+    /// and fine time (in units of 2<sup>&minus;8&times;`FINE`</sup> seconds).
+    pub const fn new(coarse: u64, fine: u64) -> Result<Self, Error> {
+        let () = Self::CHECK;
+
+        if coarse > Self::MAX_COARSE || fine > Self::MAX_FINE {
+            return Err(Error::TimeOutOfRange);
+        }
+
+        Ok(Self { coarse, fine })
+    }
+
+    /// Returns the coarse time, in seconds.
+    pub const fn coarse(&self) -> u64 {
+        self.coarse
+    }
+
+    /// Returns the fine time, in units of 2<sup>&minus;8&times;`FINE`</sup> seconds.
+    pub const fn fine(&self) -> u64 {
+        self.fine
+    }
+
+    /// Creates the time code for `duration` after the epoch, rounded down to the time code's resolution.
+    pub fn from_duration(duration: Duration) -> Result<Self, Error> {
+        Self::from_units(duration_to_units(duration, FINE)).ok_or(Error::TimeOutOfRange)
+    }
+
+    /// Returns the time since the epoch, rounded down to the nanosecond.
+    pub fn to_duration(&self) -> Duration {
+        units_to_duration(self.units(), FINE)
+    }
+
+    /// Reads a time code (without P-field) from the start of `bytes`.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let () = Self::CHECK;
+        check_len(bytes, Self::LEN)?;
+
+        Ok(Self {
+            coarse: read_be(&bytes[..COARSE]),
+            fine: read_be(&bytes[COARSE..Self::LEN]),
+        })
+    }
+
+    /// Writes the time code (without P-field) to the start of `out`,
+    /// returning the number of bytes written.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        check_len(out, Self::LEN)?;
+
+        write_be(&mut out[..COARSE], self.coarse);
+        write_be(&mut out[COARSE..Self::LEN], self.fine);
+        Ok(Self::LEN)
+    }
+
+    /// Reads a time code preceded by its P-field from the start of `bytes`,
+    /// returning it along with the epoch the P-field names.
+    ///
+    /// Fails with [`Error::InvalidPField`] if the P-field doesn't describe
+    /// a CUC time code with `COARSE` octets of coarse time and `FINE` of fine time.
+    pub fn decode_with_p_field(bytes: &[u8]) -> Result<(Self, TimeCodeEpoch), Error> {
+        let () = Self::CHECK;
+        check_len(bytes, 1)?;
+
+        let epoch = match (bytes[0] >> 4) & 0b111 {
+            0b001 => TimeCodeEpoch::Ccsds,
+            0b010 => TimeCodeEpoch::Agency,
+            _ => return Err(Error::InvalidPField(bytes[0])),
+        };
+
+        check_len(bytes, Self::P_FIELD_LEN)?;
+        if bytes[..Self::P_FIELD_LEN] != Self::p_field(epoch)[..Self::P_FIELD_LEN] {
+            return Err(Error::InvalidPField(bytes[0]));
+        }
+
+        Ok((Self::decode(&bytes[Self::P_FIELD_LEN..])?, epoch))
+    }
+
+    /// Writes the time code preceded by a P-field naming `epoch` to the start of `out`,
+    /// returning the number of bytes written.
+    pub fn encode_with_p_field(
+        &self,
+        epoch: TimeCodeEpoch,
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        check_len(out, Self::P_FIELD_LEN + Self::LEN)?;
+
+        out[..Self::P_FIELD_LEN].copy_from_slice(&Self::p_field(epoch)[..Self::P_FIELD_LEN]);
+        Ok(Self::P_FIELD_LEN + self.encode(&mut out[Self::P_FIELD_LEN..])?)
+    }
+
+    /// Returns `self + duration`, or `None` if that's out of the time code's range.
+    ///
+    /// `duration` is rounded down to the time code's resolution.
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        Self::from_units(
+            self.units()
+                .checked_add(duration_to_units(duration, FINE))?,
+        )
+    }
+
+    /// Returns `self - duration`, or `None` if that's before the epoch.
+    ///
+    /// `duration` is rounded down to the time code's resolution.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        Self::from_units(
+            self.units()
+                .checked_sub(duration_to_units(duration, FINE))?,
+        )
+    }
+
+    /// Returns the time elapsed from `earlier` to `self`, or `None` if `earlier` is later than `self`.
+    pub fn checked_duration_since(&self, earlier: Self) -> Option<Duration> {
+        Some(units_to_duration(
+            self.units().checked_sub(earlier.units())?,
+            FINE,
+        ))
+    }
+
+    /// The time code's P-field, padded out to two bytes.
+    const fn p_field(epoch: TimeCodeEpoch) -> [u8; 2] {
+        let time_code_id = match epoch {
+            TimeCodeEpoch::Ccsds => 0b001,
+            TimeCodeEpoch::Agency => 0b010,
+        };
+        let basic_coarse = if COARSE > 4 { 4 } else { COARSE };
+        let basic_fine = if FINE > 3 { 3 } else { FINE };
+
+        let mut p_field = [
+            time_code_id << 4 | ((basic_coarse - 1) << 2) as u8 | basic_fine as u8,
+            0,
+        ];
+        if Self::P_FIELD_LEN == 2 {
+            p_field[0] |= 0x80;
+            p_field[1] = ((COARSE - basic_coarse) << 5 | (FINE - basic_fine) << 2) as u8;
+        }
+        p_field
+    }
+
+    /// The time since the epoch, in units of the time code's resolution.
+    fn units(&self) -> u128 {
+        (self.coarse as u128) << (8 * FINE) | self.fine as u128
+    }
+
+    fn from_units(units: u128) -> Option<Self> {
+        let () = Self::CHECK;
+
+        if units >> (8 * Self::LEN) != 0 {
+            return None;
+        }
+
+        Some(Self {
+            coarse: (units >> (8 * FINE)) as u64,
+            fine: units as u64 & Self::MAX_FINE,
+        })
+    }
+}
+
+/// A CCSDS Day Segmented code time: a count of days (`DAYS` octets of it, 2 or 3),
+/// the milliseconds of the day, and the sub-milliseconds of the millisecond
+/// (`SUBMS` octets of it: 0 for none, 2 for microseconds or 4 for picoseconds), since some epoch.
+///
+/// Other values of `DAYS` and `SUBMS` fail to compile.
+///
+/// The milliseconds of the day may run up to 86 400 999, to allow for a leap second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cds<const DAYS: usize, const SUBMS: usize> {
+    day: u32,
+    ms_of_day: u32,
+    sub_ms: u32,
+}
+
+impl<const DAYS: usize, const SUBMS: usize> Cds<DAYS, SUBMS> {
+    const CHECK: () = assert!(
+        (DAYS == 2 || DAYS == 3) && (SUBMS == 0 || SUBMS == 2 || SUBMS == 4),
+        "CDS time codes have 2 or 3 octets of days and 0, 2 or 4 octets of sub-milliseconds"
+    );
+
+    /// The length of the time code, excluding the P-field, in bytes.
+    pub const LEN: usize = DAYS + 4 + SUBMS;
+
+    /// The length of the time code's P-field, in bytes.
+    pub const P_FIELD_LEN: usize = 1;
+
+    /// The largest possible day count.
+    pub const MAX_DAY: u32 = (1 << (8 * DAYS)) - 1;
+
+    /// The number of sub-millisecond units in a millisecond.
+    pub const SUB_MS_PER_MS: u32 = match SUBMS {
+        0 => 1,
+        2 => 1_000,
+        _ => 1_000_000_000,
+    };
+
+    /// Creates a time code from its day count, milliseconds of the day,
+    /// and sub-milliseconds of the millisecond (which must be 0 if `SUBMS` is 0).
+    pub const fn new(day: u32, ms_of_day: u32, sub_ms: u32) -> Result<Self, Error> {
+        let () = Self::CHECK;
+
+        if day > Self::MAX_DAY
+            || ms_of_day >= MILLIS_PER_DAY + 1_000
+            || (SUBMS == 0 && sub_ms != 0)
+            || (SUBMS != 0 && sub_ms >= Self::SUB_MS_PER_MS)
+        {
+            return Err(Error::TimeOutOfRange);
+        }
+
+        Ok(Self {
+            day,
+            ms_of_day,
+            sub_ms,
+        })
+    }
+
+    /// Returns the number of days since the epoch.
+    pub const fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// Returns the milliseconds of the day.
+    pub const fn ms_of_day(&self) -> u32 {
+        self.ms_of_day
+    }
+
+    /// Returns the sub-milliseconds of the millisecond, in units of 1/[`Self::SUB_MS_PER_MS`] ms.
+    pub const fn sub_ms(&self) -> u32 {
+        self.sub_ms
+    }
+
+    /// Creates the time code for `duration` after the epoch, rounded down to the time code's resolution.
+    pub fn from_duration(duration: Duration) -> Result<Self, Error> {
+        Self::from_units(cds_duration_to_units(duration, Self::SUB_MS_PER_MS))
+            .ok_or(Error::TimeOutOfRange)
+    }
+
+    /// Returns the time since the epoch, rounded down to the nanosecond.
+    ///
+    /// Days are taken to be 86 400 seconds long, so a time during a leap second
+    /// maps to the same duration as the first second of the following day.
+    pub fn to_duration(&self) -> Duration {
+        cds_units_to_duration(self.units(), Self::SUB_MS_PER_MS)
+    }
+
+    /// Reads a time code (without P-field) from the start of `bytes`.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let () = Self::CHECK;
+        check_len(bytes, Self::LEN)?;
+
+        Self::new(
+            read_be(&bytes[..DAYS]) as u32,
+            read_be(&bytes[DAYS..DAYS + 4]) as u32,
+            read_be(&bytes[DAYS + 4..Self::LEN]) as u32,
+        )
+    }
+
+    /// Writes the time code (without P-field) to the start of `out`,
+    /// returning the number of bytes written.
+    pub fn encode(&self, out: &mut [u8]) -> Result<usize, Error> {
+        check_len(out, Self::LEN)?;
+
+        write_be(&mut out[..DAYS], self.day as u64);
+        write_be(&mut out[DAYS..DAYS + 4], self.ms_of_day as u64);
+        write_be(&mut out[DAYS + 4..Self::LEN], self.sub_ms as u64);
+        Ok(Self::LEN)
+    }
+
+    /// Reads a time code preceded by its P-field from the start of `bytes`,
+    /// returning it along with the epoch the P-field names.
+    ///
+    /// Fails with [`Error::InvalidPField`] if the P-field doesn't describe
+    /// a CDS time code with `DAYS` octets of days and `SUBMS` of sub-milliseconds.
+    pub fn decode_with_p_field(bytes: &[u8]) -> Result<(Self, TimeCodeEpoch), Error> {
+        let () = Self::CHECK;
+        check_len(bytes, Self::P_FIELD_LEN)?;
+
+        let epoch = if bytes[0] & 0x08 == 0 {
+            TimeCodeEpoch::Ccsds
+        } else {
+            TimeCodeEpoch::Agency
+        };
+        if bytes[0] != Self::p_field(epoch) {
+            return Err(Error::InvalidPField(bytes[0]));
+        }
+
+        Ok((Self::decode(&bytes[Self::P_FIELD_LEN..])?, epoch))
+    }
+
+    /// Writes the time code preceded by a P-field naming `epoch` to the start of `out`,
+    /// returning the number of bytes written.
+    pub fn encode_with_p_field(
+        &self,
+        epoch: TimeCodeEpoch,
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        check_len(out, Self::P_FIELD_LEN + Self::LEN)?;
+
+        out[0] = Self::p_field(epoch);
+        Ok(Self::P_FIELD_LEN + self.encode(&mut out[Self::P_FIELD_LEN..])?)
+    }
+
+    /// Returns `self + duration`, or `None` if that's out of the time code's range.
+    ///
+    /// `duration` is rounded down to the time code's resolution.
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        let units = cds_duration_to_units(duration, Self::SUB_MS_PER_MS);
+        Self::from_units(self.units().checked_add(units)?)
+    }
+
+    /// Returns `self - duration`, or `None` if that's before the epoch.
+    ///
+    /// `duration` is rounded down to the time code's resolution.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        let units = cds_duration_to_units(duration, Self::SUB_MS_PER_MS);
+        Self::from_units(self.units().checked_sub(units)?)
+    }
+
+    /// Returns the time elapsed from `earlier` to `self`, or `None` if `earlier` is later than `self`.
+    pub fn checked_duration_since(&self, earlier: Self) -> Option<Duration> {
+        let units = self.units().checked_sub(earlier.units())?;
+        Some(cds_units_to_duration(units, Self::SUB_MS_PER_MS))
+    }
+
+    const fn p_field(epoch: TimeCodeEpoch) -> u8 {
+        let epoch_bit = match epoch {
+            TimeCodeEpoch::Ccsds => 0,
+            TimeCodeEpoch::Agency => 0x08,
+        };
+        let day_bit = if DAYS == 3 { 0x04 } else { 0 };
+
+        0b100 << 4 | epoch_bit | day_bit | (SUBMS / 2) as u8
+    }
+
+    /// The time since the epoch, in units of the time code's resolution.
+    fn units(&self) -> u128 {
+        (self.day as u128 * MILLIS_PER_DAY as u128 + self.ms_of_day as u128)
+            * Self::SUB_MS_PER_MS as u128
+            + self.sub_ms as u128
+    }
+
+    fn from_units(units: u128) -> Option<Self> {
+        let () = Self::CHECK;
+
+        let millis = units / Self::SUB_MS_PER_MS as u128;
+        let day = millis / MILLIS_PER_DAY as u128;
+        if day > Self::MAX_DAY as u128 {
+            return None;
+        }
+
+        Some(Self {
+            day: day as u32,
+            ms_of_day: (millis % MILLIS_PER_DAY as u128) as u32,
+            sub_ms: if SUBMS == 0 {
+                0
+            } else {
+                (units % Self::SUB_MS_PER_MS as u128) as u32
+            },
+        })
+    }
+}
+
+macro_rules! time_code_ops {
+    ($time_code:ident, $a:ident, $b:ident) => {
+        impl<const $a: usize, const $b: usize> From<$time_code<$a, $b>> for Duration {
+            fn from(time: $time_code<$a, $b>) -> Duration {
+                time.to_duration()
+            }
+        }
+
+        impl<const $a: usize, const $b: usize> TryFrom<Duration> for $time_code<$a, $b> {
+            type Error = Error;
+
+            fn try_from(duration: Duration) -> Result<Self, Error> {
+                Self::from_duration(duration)
+            }
+        }
+
+        impl<const $a: usize, const $b: usize> Add<Duration> for $time_code<$a, $b> {
+            type Output = Self;
+
+            fn add(self, duration: Duration) -> Self {
+                self.checked_add(duration)
+                    .expect("overflow when adding duration to time code")
+            }
+        }
+
+        impl<const $a: usize, const $b: usize> AddAssign<Duration> for $time_code<$a, $b> {
+            fn add_assign(&mut self, duration: Duration) {
+                *self = *self + duration;
+            }
+        }
+
+        impl<const $a: usize, const $b: usize> Sub<Duration> for $time_code<$a, $b> {
+            type Output = Self;
+
+            fn sub(self, duration: Duration) -> Self {
+                self.checked_sub(duration)
+                    .expect("overflow when subtracting duration from time code")
+            }
+        }
+
+        impl<const $a: usize, const $b: usize> SubAssign<Duration> for $time_code<$a, $b> {
+            fn sub_assign(&mut self, duration: Duration) {
+                *self = *self - duration;
+            }
+        }
+
+        impl<const $a: usize, const $b: usize> Sub for $time_code<$a, $b> {
+            type Output = Duration;
+
+            fn sub(self, earlier: Self) -> Duration {
+                self.checked_duration_since(earlier)
+                    .expect("overflow when subtracting time codes")
+            }
+        }
+    };
+}
+
+time_code_ops!(Cuc, COARSE, FINE);
+time_code_ops!(Cds, DAYS, SUBMS);
+
+/// Implements [`TimeFormat`] for each CUC time code with `$coarse` octets of coarse time
+/// and one of `$fine` octets of fine time.
+macro_rules! cuc_time_formats {
+    ($coarse:literal; $($fine:literal)*) => {
+        $(
+            impl TimeFormat for Cuc<$coarse, $fine> {
+                type Header = [u8; $coarse + $fine];
+                type Timestamp = Self;
+
+                fn timestamp(header: &[u8]) -> Self {
+                    Self::decode(header).unwrap()
+                }
+
+                fn set_timestamp(header: &mut [u8], timestamp: Self) {
+                    timestamp.encode(header).unwrap();
+                }
+
+                fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> Self {
+                    let (seconds, nanoseconds) = normalize(seconds, nanoseconds);
+                    // the seconds wrap around, as they do in cFE's own formats
+                    let mut time = Self::from_duration(Duration::new(0, nanoseconds)).unwrap();
+                    time.coarse = seconds & Self::MAX_COARSE;
+                    time
+                }
+
+                fn to_duration(timestamp: Self) -> Duration {
+                    timestamp.to_duration()
+                }
+            }
+        )*
+    };
+}
+
+cuc_time_formats!(1; 0 1 2 3 4 5 6);
+cuc_time_formats!(2; 0 1 2 3 4 5 6);
+cuc_time_formats!(3; 0 1 2 3 4 5 6);
+cuc_time_formats!(4; 0 1 2 3 4 5 6);
+cuc_time_formats!(5; 0 1 2 3 4 5 6);
+cuc_time_formats!(6; 0 1 2 3 4 5 6);
+cuc_time_formats!(7; 0 1 2 3 4 5 6);
+
+/// Implements [`TimeFormat`] for each CDS time code with `$days` octets of days
+/// and one of `$subms` octets of sub-milliseconds.
+///
+/// Timestamps read out of a header aren't range-checked, as [`TimeFormat::timestamp`] can't fail;
+/// use [`Cds::decode`] on the header bytes for that.
+macro_rules! cds_time_formats {
+    ($days:literal; $($subms:literal)*) => {
+        $(
+            impl TimeFormat for Cds<$days, $subms> {
+                type Header = [u8; $days + 4 + $subms];
+                type Timestamp = Self;
+
+                fn timestamp(header: &[u8]) -> Self {
+                    Self {
+                        day: read_be(&header[..$days]) as u32,
+                        ms_of_day: read_be(&header[$days..$days + 4]) as u32,
+                        sub_ms: read_be(&header[$days + 4..Self::LEN]) as u32,
+                    }
+                }
+
+                fn set_timestamp(header: &mut [u8], timestamp: Self) {
+                    timestamp.encode(header).unwrap();
+                }
+
+                fn from_secs_nanos(seconds: u64, nanoseconds: u32) -> Self {
+                    let (seconds, nanoseconds) = normalize(seconds, nanoseconds);
+                    // the days wrap around, as the seconds do in cFE's own formats
+                    let seconds = seconds % ((Self::MAX_DAY as u64 + 1) * 86_400);
+                    Self::from_duration(Duration::new(seconds, nanoseconds)).unwrap()
+                }
+
+                fn to_duration(timestamp: Self) -> Duration {
+                    timestamp.to_duration()
+                }
+            }
+        )*
+    };
+}
+
+cds_time_formats!(2; 0 2 4);
+cds_time_formats!(3; 0 2 4);
+
+fn check_len(bytes: &[u8], len: usize) -> Result<(), Error> {
+    if bytes.len() < len {
+        return Err(Error::Truncated {
+            expected: len,
+            actual: bytes.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads a big-endian unsigned integer of up to eight bytes.
+fn read_be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, &byte| acc << 8 | byte as u64)
+}
+
+/// Writes the low `bytes.len()` bytes of `value` as a big-endian unsigned integer.
+fn write_be(bytes: &mut [u8], value: u64) {
+    let be = value.to_be_bytes();
+    bytes.copy_from_slice(&be[be.len() - bytes.len()..]);
+}
+
+/// Converts `duration` to units of 2<sup>&minus;8&times;`fine`</sup> seconds, rounding down.
+fn duration_to_units(duration: Duration, fine: usize) -> u128 {
+    let subsecs = ((duration.subsec_nanos() as u128) << (8 * fine)) / NANOS_PER_SEC;
+    (duration.as_secs() as u128) << (8 * fine) | subsecs
+}
+
+/// Converts a time in units of 2<sup>&minus;8&times;`fine`</sup> seconds to a `Duration`, rounding down.
+fn units_to_duration(units: u128, fine: usize) -> Duration {
+    let subsecs = units & ((1 << (8 * fine)) - 1);
+    let nanos = (subsecs * NANOS_PER_SEC) >> (8 * fine);
+
+    Duration::new((units >> (8 * fine)) as u64, nanos as u32)
+}
+
+/// Converts `duration` to units of 1/`sub_ms_per_ms` milliseconds, rounding down.
+fn cds_duration_to_units(duration: Duration, sub_ms_per_ms: u32) -> u128 {
+    duration.as_nanos() * sub_ms_per_ms as u128 / 1_000_000
+}
+
+/// Converts a time in units of 1/`sub_ms_per_ms` milliseconds to a `Duration`, rounding down.
+fn cds_units_to_duration(units: u128, sub_ms_per_ms: u32) -> Duration {
+    let nanos = units * 1_000_000 / sub_ms_per_ms as u128;
+    Duration::new(
+        (nanos / NANOS_PER_SEC) as u64,
+        (nanos % NANOS_PER_SEC) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuc_p_fields() {
+        let mut out = [0; 16];
+        let time = Cuc::<4, 2>::new(0x0102_0304, 0x0506).unwrap();
+
+        assert_eq!(
+            time.encode_with_p_field(TimeCodeEpoch::Ccsds, &mut out),
+            Ok(7)
+        );
+        assert_eq!(out[..7], [0x1E, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+        time.encode_with_p_field(TimeCodeEpoch::Agency, &mut out)
+            .unwrap();
+        assert_eq!(out[0], 0x2E);
+        assert_eq!(
+            Cuc::<4, 2>::decode_with_p_field(&out[..7]),
+            Ok((time, TimeCodeEpoch::Agency))
+        );
+
+        // more than 4 octets of coarse time or 3 of fine time need the second P-field octet
+        let time = Cuc::<5, 4>::new(1, 2).unwrap();
+        assert_eq!(
+            time.encode_with_p_field(TimeCodeEpoch::Ccsds, &mut out),
+            Ok(11)
+        );
+        assert_eq!(out[..11], [0x9F, 0x24, 0, 0, 0, 0, 1, 0, 0, 0, 2]);
+
+        Cuc::<1, 0>::default()
+            .encode_with_p_field(TimeCodeEpoch::Ccsds, &mut out)
+            .unwrap();
+        assert_eq!(out[0], 0x10);
+
+        assert_eq!(
+            Cuc::<4, 2>::decode_with_p_field(&[0x1D, 0, 0, 0, 0, 0, 0]),
+            Err(Error::InvalidPField(0x1D))
+        );
+    }
+
+    #[test]
+    fn cds_p_fields() {
+        let mut out = [0; 16];
+        let time = Cds::<2, 2>::new(1, 2, 3).unwrap();
+
+        assert_eq!(
+            time.encode_with_p_field(TimeCodeEpoch::Ccsds, &mut out),
+            Ok(9)
+        );
+        assert_eq!(
+            out[..9],
+            [0x41, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x03]
+        );
+        assert_eq!(
+            Cds::<2, 2>::decode_with_p_field(&out[..9]),
+            Ok((time, TimeCodeEpoch::Ccsds))
+        );
+
+        Cds::<2, 0>::default()
+            .encode_with_p_field(TimeCodeEpoch::Ccsds, &mut out)
+            .unwrap();
+        assert_eq!(out[0], 0x40);
+        Cds::<2, 0>::default()
+            .encode_with_p_field(TimeCodeEpoch::Agency, &mut out)
+            .unwrap();
+        assert_eq!(out[0], 0x48);
+        Cds::<3, 4>::default()
+            .encode_with_p_field(TimeCodeEpoch::Ccsds, &mut out)
+            .unwrap();
+        assert_eq!(out[0], 0x46);
+    }
+
+    #[test]
+    fn time_codes_from_durations() {
+        let cuc = Cuc::<4, 2>::from_duration(Duration::from_millis(1_500)).unwrap();
+        assert_eq!((cuc.coarse(), cuc.fine()), (1, 0x8000));
+        assert_eq!(cuc.to_duration(), Duration::from_millis(1_500));
+
+        let cds = Cds::<2, 2>::from_duration(Duration::new(86_400, 1_002_999)).unwrap();
+        assert_eq!((cds.day(), cds.ms_of_day(), cds.sub_ms()), (1, 1, 2));
+        assert_eq!(cds.to_duration(), Duration::new(86_400, 1_002_000));
+
+        assert_eq!(
+            Cuc::<1, 0>::from_duration(Duration::from_secs(256)),
+            Err(Error::TimeOutOfRange)
+        );
+    }
+
+    #[test]
+    fn time_formats_carry_whole_seconds_out_of_nanoseconds() {
+        let cuc = <Cuc<4, 2> as TimeFormat>::from_secs_nanos(10, 2_500_000_000);
+        assert_eq!((cuc.coarse(), cuc.fine()), (12, 0x8000));
+        let cuc = <Cuc<1, 0> as TimeFormat>::from_secs_nanos(255, 1_000_000_000);
+        assert_eq!(cuc.coarse(), 0);
+
+        let cds = <Cds<2, 2> as TimeFormat>::from_secs_nanos(86_399, 1_500_000_000);
+        assert_eq!((cds.day(), cds.ms_of_day(), cds.sub_ms()), (1, 500, 0));
+        // the last second of the last day, plus a second, wraps around to the epoch
+        let cds = <Cds<2, 0> as TimeFormat>::from_secs_nanos(65_536 * 86_400 - 1, 1_000_000_000);
+        assert_eq!((cds.day(), cds.ms_of_day()), (0, 0));
+    }
+}
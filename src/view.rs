@@ -0,0 +1,532 @@
+//! Borrowed views of cFS packets, which validate and access packet headers in place
+//! rather than copying the packet into a [`Command`](crate::Command) or [`Telemetry`](crate::Telemetry).
+
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+use crate::raw::{primary_header, primary_header_mut};
+use crate::{
+    cfe_checksum, check_checksum, check_command_header, check_telemetry_header, expected_checksum,
+    telemetry_header_len, AnyBitPattern, Cfe32_16, CommandMsgId, Error, MsgIdScheme, MsgIdV1,
+    PacketType, PrimaryHeader, TelemetryMsgId, TimeConfig, TimeFormat, TimeSource, UtcTime,
+    COMMAND_HEADER_LEN, MAX_FUNCTION_CODE,
+};
+
+/// Copies `payload` into a `T`, if `payload` is exactly as long as a `T`.
+fn read_payload<T: AnyBitPattern>(payload: &[u8]) -> Result<T, Error> {
+    if payload.len() != size_of::<T>() {
+        return Err(Error::WrongLength {
+            expected: size_of::<T>(),
+            actual: payload.len(),
+        });
+    }
+
+    // Safety: payload is the right length, and every byte pattern is a valid T.
+    Ok(unsafe { core::ptr::read_unaligned(payload.as_ptr() as *const T) })
+}
+
+/// Checks that `bytes` can hold at least `header_len` bytes of headers.
+fn check_min_len(bytes: &[u8], header_len: usize) -> Result<(), Error> {
+    if bytes.len() < header_len {
+        return Err(Error::Truncated {
+            expected: header_len,
+            actual: bytes.len(),
+        });
+    }
+    Ok(())
+}
+
+/// A borrowed view of a byte buffer holding a cFS-flavor CCSDS command packet,
+/// whose message ID is mapped onto the headers by `M`.
+#[derive(Clone, Copy, Debug)]
+pub struct CommandView<'a, M: MsgIdScheme> {
+    bytes: &'a [u8],
+    msg_ids: PhantomData<M>,
+}
+
+/// A mutable borrowed view of a byte buffer holding a cFS-flavor CCSDS command packet,
+/// whose message ID is mapped onto the headers by `M`.
+#[derive(Debug)]
+pub struct CommandViewMut<'a, M: MsgIdScheme> {
+    bytes: &'a mut [u8],
+    msg_ids: PhantomData<M>,
+}
+
+/// A borrowed view of a byte buffer holding a cFS-flavor CCSDS telemetry packet,
+/// whose message ID is mapped onto the headers by `M` and whose timestamp is in the time format `F`.
+#[derive(Clone, Copy, Debug)]
+pub struct TelemetryView<'a, M: MsgIdScheme, F: TimeFormat> {
+    bytes: &'a [u8],
+    msg_ids: PhantomData<M>,
+    time_format: PhantomData<F>,
+}
+
+/// A mutable borrowed view of a byte buffer holding a cFS-flavor CCSDS telemetry packet,
+/// whose message ID is mapped onto the headers by `M` and whose timestamp is in the time format `F`.
+#[derive(Debug)]
+pub struct TelemetryViewMut<'a, M: MsgIdScheme, F: TimeFormat> {
+    bytes: &'a mut [u8],
+    msg_ids: PhantomData<M>,
+    time_format: PhantomData<F>,
+}
+
+/// A borrowed view of a command packet using cFE's default message ID mapping.
+pub type CommandRef<'a> = CommandView<'a, MsgIdV1>;
+
+/// A mutable borrowed view of a command packet using cFE's default message ID mapping.
+pub type CommandMut<'a> = CommandViewMut<'a, MsgIdV1>;
+
+/// A borrowed view of a telemetry packet using cFE's default message ID mapping and packet time format.
+pub type TelemetryRef<'a> = TelemetryView<'a, MsgIdV1, Cfe32_16>;
+
+/// A mutable borrowed view of a telemetry packet using cFE's default message ID mapping and packet time format.
+pub type TelemetryMut<'a> = TelemetryViewMut<'a, MsgIdV1, Cfe32_16>;
+
+impl<'a, M: MsgIdScheme> CommandView<'a, M> {
+    /// If `bytes` holds exactly one command packet with sane header values, returns a view of it.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        check_min_len(bytes, COMMAND_HEADER_LEN)?;
+        check_command_header::<M>(bytes, bytes.len())?;
+        Ok(Self::new_unchecked(bytes))
+    }
+
+    /// Wraps `bytes`, which must already have been checked to hold a command packet.
+    pub(crate) fn new_unchecked(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            msg_ids: PhantomData,
+        }
+    }
+
+    /// Returns the whole packet as a sequence of bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the message's CCSDS primary header.
+    pub fn primary_header(&self) -> &'a PrimaryHeader {
+        primary_header(self.bytes)
+    }
+
+    /// Returns the message's message ID.
+    pub fn msg_id(&self) -> CommandMsgId<M> {
+        CommandMsgId::new_unchecked(M::msg_id(self.bytes))
+    }
+
+    /// Returns the message's application process identifier.
+    pub fn apid(&self) -> u16 {
+        self.primary_header().apid()
+    }
+
+    /// Returns the message's packet type.
+    pub fn packet_type(&self) -> PacketType {
+        self.primary_header().packet_type()
+    }
+
+    /// Returns the message's command code.
+    pub fn function_code(&self) -> u16 {
+        self.bytes[6] as u16
+    }
+
+    /// Returns the message's sequence number.
+    pub fn sequence_number(&self) -> u16 {
+        self.primary_header().sequence_count()
+    }
+
+    /// Returns the contents of the message's checksum field.
+    pub fn checksum(&self) -> u8 {
+        self.bytes[7]
+    }
+
+    /// Computes the checksum the message's checksum field should contain,
+    /// given the rest of the message's contents.
+    pub fn compute_checksum(&self) -> u8 {
+        expected_checksum(self.bytes, self.checksum())
+    }
+
+    /// Returns whether the message's checksum field is consistent with its contents.
+    pub fn is_checksum_valid(&self) -> bool {
+        cfe_checksum(self.bytes) == 0
+    }
+
+    /// Returns an error describing the discrepancy if the message's checksum field is inconsistent with its contents.
+    pub fn validate_checksum(&self) -> Result<(), Error> {
+        check_checksum(self.bytes, self.checksum())
+    }
+
+    /// Returns the message's payload: everything after the headers.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[COMMAND_HEADER_LEN..]
+    }
+
+    /// Copies the message's payload out into a `T`, if the payload is exactly as long as a `T`.
+    pub fn payload_as<T: AnyBitPattern>(&self) -> Result<T, Error> {
+        read_payload(self.payload())
+    }
+}
+
+impl<'a, M: MsgIdScheme> CommandViewMut<'a, M> {
+    /// If `bytes` holds exactly one command packet with sane header values, returns a mutable view of it.
+    pub fn new(bytes: &'a mut [u8]) -> Result<Self, Error> {
+        CommandView::<M>::new(bytes)?;
+        Ok(Self::new_unchecked(bytes))
+    }
+
+    /// Wraps `bytes`, which must already have been checked to hold a command packet.
+    pub(crate) fn new_unchecked(bytes: &'a mut [u8]) -> Self {
+        Self {
+            bytes,
+            msg_ids: PhantomData,
+        }
+    }
+
+    /// Returns an immutable view of the packet, for access to its header fields.
+    pub fn view(&self) -> CommandView<'_, M> {
+        CommandView::new_unchecked(self.bytes)
+    }
+
+    /// Turns this view into an immutable view with the same lifetime.
+    pub fn into_ref(self) -> CommandView<'a, M> {
+        CommandView::new_unchecked(self.bytes)
+    }
+
+    /// Returns the whole packet as a sequence of bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    /// Returns the message's CCSDS primary header for modification.
+    ///
+    /// Note that this bypasses the checks done when the message ID and other fields are set;
+    /// it's on the caller to keep the header consistent with the rest of the message.
+    pub fn primary_header_mut(&mut self) -> &mut PrimaryHeader {
+        primary_header_mut(self.bytes)
+    }
+
+    /// Sets the message's message ID to `msg_id`.
+    pub fn set_msg_id(&mut self, msg_id: CommandMsgId<M>) {
+        M::set_msg_id(self.bytes, msg_id.get());
+    }
+
+    /// If `function_code` is a valid command code, sets the message's function code to `function_code`.
+    pub fn set_function_code(&mut self, function_code: u16) -> Result<(), Error> {
+        if function_code <= MAX_FUNCTION_CODE {
+            self.bytes[6] = function_code as u8;
+            Ok(())
+        } else {
+            Err(Error::InvalidFunctionCode(function_code))
+        }
+    }
+
+    /// If `sequence_number` fits in 14 bits, sets the message's sequence number to `sequence_number`.
+    pub fn set_sequence_number(&mut self, sequence_number: u16) -> Result<(), Error> {
+        self.primary_header_mut()
+            .set_sequence_count(sequence_number)
+    }
+
+    /// Increment the message's sequence number.
+    pub fn increment_sequence_num(&mut self) {
+        self.primary_header_mut().increment_sequence_count();
+    }
+
+    /// Sets the message's checksum field to the checksum of the rest of the message.
+    pub fn set_checksum(&mut self) {
+        self.bytes[7] = self.view().compute_checksum();
+    }
+
+    /// Returns the message's payload for modification.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes[COMMAND_HEADER_LEN..]
+    }
+}
+
+impl<'a, M: MsgIdScheme, F: TimeFormat> TelemetryView<'a, M, F> {
+    /// If `bytes` holds exactly one telemetry packet with sane header values, returns a view of it.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        check_min_len(bytes, telemetry_header_len::<F>())?;
+        check_telemetry_header::<M>(bytes, bytes.len())?;
+        Ok(Self::new_unchecked(bytes))
+    }
+
+    /// Wraps `bytes`, which must already have been checked to hold a telemetry packet.
+    pub(crate) fn new_unchecked(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            msg_ids: PhantomData,
+            time_format: PhantomData,
+        }
+    }
+
+    /// Returns the whole packet as a sequence of bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the message's CCSDS primary header.
+    pub fn primary_header(&self) -> &'a PrimaryHeader {
+        primary_header(self.bytes)
+    }
+
+    /// Returns the message's message ID.
+    pub fn msg_id(&self) -> TelemetryMsgId<M> {
+        TelemetryMsgId::new_unchecked(M::msg_id(self.bytes))
+    }
+
+    /// Returns the message's application process identifier.
+    pub fn apid(&self) -> u16 {
+        self.primary_header().apid()
+    }
+
+    /// Returns the message's packet type.
+    pub fn packet_type(&self) -> PacketType {
+        self.primary_header().packet_type()
+    }
+
+    /// Returns the message's timestamp, in the form given by the time format `F`.
+    pub fn timestamp(&self) -> F::Timestamp {
+        F::timestamp(&self.bytes[PrimaryHeader::LEN..])
+    }
+
+    /// Returns the message's timestamp as a UTC time,
+    /// taking the spacecraft clock to be configured as in `config`.
+    pub fn timestamp_utc(&self, config: &TimeConfig) -> UtcTime {
+        config.to_utc(F::to_duration(self.timestamp()))
+    }
+
+    /// Returns the message's sequence number.
+    pub fn sequence_number(&self) -> u16 {
+        self.primary_header().sequence_count()
+    }
+
+    /// Returns the message's payload: everything after the headers.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[telemetry_header_len::<F>()..]
+    }
+
+    /// Copies the message's payload out into a `T`, if the payload is exactly as long as a `T`.
+    pub fn payload_as<T: AnyBitPattern>(&self) -> Result<T, Error> {
+        read_payload(self.payload())
+    }
+}
+
+impl<'a, M: MsgIdScheme, F: TimeFormat> TelemetryViewMut<'a, M, F> {
+    /// If `bytes` holds exactly one telemetry packet with sane header values, returns a mutable view of it.
+    pub fn new(bytes: &'a mut [u8]) -> Result<Self, Error> {
+        TelemetryView::<M, F>::new(bytes)?;
+        Ok(Self::new_unchecked(bytes))
+    }
+
+    /// Wraps `bytes`, which must already have been checked to hold a telemetry packet.
+    pub(crate) fn new_unchecked(bytes: &'a mut [u8]) -> Self {
+        Self {
+            bytes,
+            msg_ids: PhantomData,
+            time_format: PhantomData,
+        }
+    }
+
+    /// Returns an immutable view of the packet, for access to its header fields.
+    pub fn view(&self) -> TelemetryView<'_, M, F> {
+        TelemetryView::new_unchecked(self.bytes)
+    }
+
+    /// Turns this view into an immutable view with the same lifetime.
+    pub fn into_ref(self) -> TelemetryView<'a, M, F> {
+        TelemetryView::new_unchecked(self.bytes)
+    }
+
+    /// Returns the whole packet as a sequence of bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    /// Returns the message's CCSDS primary header for modification.
+    ///
+    /// Note that this bypasses the checks done when the message ID and other fields are set;
+    /// it's on the caller to keep the header consistent with the rest of the message.
+    pub fn primary_header_mut(&mut self) -> &mut PrimaryHeader {
+        primary_header_mut(self.bytes)
+    }
+
+    /// Sets the message's message ID to `msg_id`.
+    pub fn set_msg_id(&mut self, msg_id: TelemetryMsgId<M>) {
+        M::set_msg_id(self.bytes, msg_id.get());
+    }
+
+    /// If `sequence_number` fits in 14 bits, sets the message's sequence number to `sequence_number`.
+    pub fn set_sequence_number(&mut self, sequence_number: u16) -> Result<(), Error> {
+        self.primary_header_mut()
+            .set_sequence_count(sequence_number)
+    }
+
+    /// Increment the message's sequence number.
+    pub fn increment_sequence_num(&mut self) {
+        self.primary_header_mut().increment_sequence_count();
+    }
+
+    /// Sets the message's timestamp to
+    /// `seconds` seconds + `nanoseconds` nanoseconds
+    /// since the flight-software epoch, rounded down to the resolution of the time format `F`.
+    pub fn set_timestamp(&mut self, seconds: u64, nanoseconds: u32) {
+        self.set_timestamp_to(F::from_secs_nanos(seconds, nanoseconds));
+    }
+
+    /// Sets the message's timestamp to `timestamp`, given in the form used by the time format `F`.
+    pub fn set_timestamp_to(&mut self, timestamp: F::Timestamp) {
+        F::set_timestamp(&mut self.bytes[PrimaryHeader::LEN..], timestamp);
+    }
+
+    /// Sets the message's timestamp to the current time according to `source`.
+    pub fn timestamp_with<S: TimeSource + ?Sized>(&mut self, source: &S) -> Result<(), Error> {
+        let since_epoch = source.now()?;
+
+        self.set_timestamp(since_epoch.as_secs(), since_epoch.subsec_nanos());
+        Ok(())
+    }
+
+    /// Returns the message's payload for modification.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes[telemetry_header_len::<F>()..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, Telemetry};
+
+    fn command_bytes() -> Vec<u8> {
+        let mut cmd = Command::new(CommandMsgId::new(0x1880), 3, [1u8, 2, 3, 4]).unwrap();
+        cmd.as_bytes_with_checksum().to_vec()
+    }
+
+    fn telemetry_bytes() -> Vec<u8> {
+        let mut tlm = Telemetry::new(TelemetryMsgId::new(0x0880), [5u8, 6, 7, 8]).unwrap();
+        tlm.set_sequence_number(42).unwrap();
+        tlm.set_timestamp(100, 500_000_000);
+        tlm.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn command_views_match_owned_packets() {
+        let bytes = command_bytes();
+        let cmd = Command::<[u8; 4]>::from_bytes(&bytes).unwrap();
+        let view = CommandRef::new(&bytes).unwrap();
+
+        assert_eq!(view.msg_id(), cmd.msg_id());
+        assert_eq!(view.apid(), 0x080);
+        assert_eq!(view.packet_type(), PacketType::Command);
+        assert_eq!(view.function_code(), 3);
+        assert_eq!(view.checksum(), cmd.checksum());
+        assert!(view.is_checksum_valid());
+        assert_eq!(view.payload(), [1, 2, 3, 4]);
+        assert_eq!(view.payload_as::<[u8; 4]>(), Ok(cmd.payload));
+        assert_eq!(
+            view.payload_as::<[u8; 2]>(),
+            Err(Error::WrongLength {
+                expected: 2,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn telemetry_views_match_owned_packets() {
+        let bytes = telemetry_bytes();
+        let tlm = Telemetry::<[u8; 4]>::from_bytes(&bytes).unwrap();
+        let view = TelemetryRef::new(&bytes).unwrap();
+
+        assert_eq!(view.msg_id(), tlm.msg_id());
+        assert_eq!(view.packet_type(), PacketType::Telemetry);
+        assert_eq!(view.sequence_number(), 42);
+        assert_eq!(view.timestamp(), (100, 0x8000));
+        assert_eq!(view.timestamp(), tlm.timestamp());
+        assert_eq!(view.payload_as::<[u8; 4]>(), Ok(tlm.payload));
+        assert_eq!(
+            view.payload_as::<u64>(),
+            Err(Error::WrongLength {
+                expected: 8,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn views_validate_headers() {
+        let command = command_bytes();
+        let telemetry = telemetry_bytes();
+
+        assert_eq!(
+            CommandRef::new(&command[..5]).err(),
+            Some(Error::Truncated {
+                expected: 8,
+                actual: 5
+            })
+        );
+        assert_eq!(
+            TelemetryRef::new(&command).err(),
+            Some(Error::Truncated {
+                expected: 16,
+                actual: 12
+            })
+        );
+        assert_eq!(
+            CommandRef::new(&telemetry).err(),
+            Some(Error::InvalidVersionOrType(0x08))
+        );
+
+        let mut long = command.clone();
+        long.push(0);
+        assert_eq!(
+            CommandRef::new(&long).err(),
+            Some(Error::LengthFieldMismatch {
+                expected: 13,
+                actual: 12
+            })
+        );
+
+        // without the secondary header flag, the message ID isn't a cFS one
+        let mut no_secondary = telemetry.clone();
+        no_secondary[0] &= !0x08;
+        assert_eq!(
+            TelemetryRef::new(&no_secondary).err(),
+            Some(Error::InvalidMsgId(0x0080))
+        );
+        assert!(TelemetryMut::new(&mut no_secondary).is_err());
+    }
+
+    #[test]
+    fn mutable_views_write_in_place() {
+        let mut bytes = command_bytes();
+        let mut view = CommandMut::new(&mut bytes).unwrap();
+        view.set_msg_id(CommandMsgId::new(0x1881));
+        view.set_function_code(0x7F).unwrap();
+        assert_eq!(
+            view.set_function_code(0x80),
+            Err(Error::InvalidFunctionCode(0x80))
+        );
+        view.set_sequence_number(0x1234).unwrap();
+        view.payload_mut()[0] = 0xAA;
+        view.set_checksum();
+        assert!(view.view().is_checksum_valid());
+
+        assert_eq!(bytes[..4], [0x18, 0x81, 0xD2, 0x34]);
+        assert_eq!(bytes[6], 0x7F);
+        assert_eq!(bytes[8..], [0xAA, 2, 3, 4]);
+        let cmd = Command::<[u8; 4]>::from_bytes(&bytes).unwrap();
+        assert!(cmd.is_checksum_valid());
+        assert_eq!(cmd.function_code(), 0x7F);
+
+        let mut bytes = telemetry_bytes();
+        let mut view = TelemetryMut::new(&mut bytes).unwrap();
+        view.set_msg_id(TelemetryMsgId::new(0x0881));
+        view.increment_sequence_num();
+        view.set_timestamp_to((0x0102_0304, 0x0506));
+        view.payload_mut().copy_from_slice(&[9, 9, 9, 9]);
+        assert_eq!(view.into_ref().sequence_number(), 43);
+
+        assert_eq!(bytes[..4], [0x08, 0x81, 0xC0, 43]);
+        assert_eq!(bytes[6..12], [1, 2, 3, 4, 5, 6]);
+        assert_eq!(bytes[16..], [9, 9, 9, 9]);
+    }
+}